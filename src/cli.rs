@@ -0,0 +1,118 @@
+use crate::error::{Error, ErrorKind};
+use crate::compiler::codegen::Target;
+use crate::scanning::emit::Emit;
+
+pub const USAGE: &str = "USAGE: luo [command] [options] <input> ([output])
+
+COMMANDS:
+    build    compile the input to lua (default)
+    check    type check the input without writing anything
+    lex      print the tokens of the input
+    parse    print the syntax tree of the input
+
+OPTIONS:
+    -o, --out <path>    where to write the lua code, `-` writes to stdout
+    -t, --target <lua>  lua version to compile for: 5.1, 5.2, 5.3, 5.4 (default) or jit
+    -e, --emit <format> how lex and parse print: text (default), json or sexp
+    -q, --quiet         only print errors
+    -h, --help          print this message
+
+An input of `-` is read from stdin.";
+pub const STDIN: &str = "<stdin>";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command { Build, Check, Lex, Parse }
+impl Command {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "build" => Some(Self::Build),
+            "check" => Some(Self::Check),
+            "lex" => Some(Self::Lex),
+            "parse" => Some(Self::Parse),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    command: Command,
+    input: Option<String>,
+    out: Option<String>,
+    target: Target,
+    emit: Emit,
+    quiet: bool,
+    help: bool,
+}
+impl Args {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let mut args = args.peekable();
+        let command = match args.peek().and_then(|x| Command::from_name(x)) {
+            Some(command) => { args.next(); command }
+            None => Command::Build
+        };
+        let (mut input, mut out, mut quiet, mut help) = (None, None, false, false);
+        let (mut target, mut emit) = (Target::Lua54, Emit::Text);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--out" => {
+                    let Some(path) = args.next() else {
+                        return Err(Error::plain(ErrorKind::Usage(format!("{arg} expects a path"))))
+                    };
+                    out = Some(path);
+                }
+                "-t" | "--target" => {
+                    let Some(name) = args.next() else {
+                        return Err(Error::plain(ErrorKind::Usage(format!("{arg} expects a lua version"))))
+                    };
+                    target = Self::parse_target(&name)?;
+                }
+                "-e" | "--emit" => {
+                    let Some(name) = args.next() else {
+                        return Err(Error::plain(ErrorKind::Usage(format!("{arg} expects a format"))))
+                    };
+                    emit = Self::parse_emit(&name)?;
+                }
+                "-q" | "--quiet" => quiet = true,
+                "-h" | "--help" => help = true,
+                _ if arg.starts_with("--out=") => out = Some(arg["--out=".len()..].to_string()),
+                _ if arg.starts_with("--target=") => target = Self::parse_target(&arg["--target=".len()..])?,
+                _ if arg.starts_with("--emit=") => emit = Self::parse_emit(&arg["--emit=".len()..])?,
+                _ if arg.starts_with('-') && arg != "-" =>
+                    return Err(Error::plain(ErrorKind::Usage(format!("unknown option {arg:?}")))),
+                _ if input.is_none() => input = Some(arg),
+                // the output path can still be given after the input
+                _ if out.is_none() && command == Command::Build => out = Some(arg),
+                _ => return Err(Error::plain(ErrorKind::Usage(format!("unexpected argument {arg:?}"))))
+            }
+        }
+        if input.is_none() && !help {
+            return Err(Error::plain(ErrorKind::Usage("no input given".to_string())))
+        }
+        if out.is_some() && command != Command::Build {
+            return Err(Error::plain(ErrorKind::Usage("only build writes an output".to_string())))
+        }
+        if emit != Emit::Text && !matches!(command, Command::Lex | Command::Parse) {
+            return Err(Error::plain(ErrorKind::Usage("only lex and parse take a format".to_string())))
+        }
+        Ok(Self { command, input, out, target, emit, quiet, help })
+    }
+    pub fn parse_target(name: &str) -> Result<Target, Error> {
+        Target::from_name(name).ok_or_else(|| Error::plain(ErrorKind::Usage(format!("unknown target {name:?}"))))
+    }
+    pub fn parse_emit(name: &str) -> Result<Emit, Error> {
+        Emit::from_name(name).ok_or_else(|| Error::plain(ErrorKind::Usage(format!("unknown format {name:?}"))))
+    }
+    pub fn command(&self) -> Command { self.command }
+    pub fn input(&self) -> Option<&str> { self.input.as_deref() }
+    pub fn out(&self) -> Option<&str> { self.out.as_deref() }
+    pub fn target(&self) -> Target { self.target }
+    pub fn emit(&self) -> Emit { self.emit }
+    pub fn quiet(&self) -> bool { self.quiet }
+    pub fn help(&self) -> bool { self.help }
+    pub fn is_stdin(&self) -> bool { self.input() == Some("-") }
+    // the name diagnostics refer to the input by
+    pub fn path(&self) -> &str {
+        if self.is_stdin() { STDIN } else { self.input().unwrap_or(STDIN) }
+    }
+}
\ No newline at end of file
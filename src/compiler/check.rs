@@ -0,0 +1,720 @@
+use std::collections::HashMap;
+use crate::error::{Error, ErrorKind};
+use crate::scanning::{nodes::{Ast, NodeId, NodeType, TableField, join}, tokens::TokenType, position::Position, symbol::Symbol};
+use super::types::Type;
+
+pub type CheckResult = Result<Type, Error>;
+
+pub fn prelude() -> HashMap<Symbol, Type> {
+    let any = || Box::new(Type::Any);
+    let mut globals = HashMap::new();
+    globals.insert(Symbol::intern("print"), Type::Function { params: vec![], varargs: Some(any()), returns: vec![] });
+    globals.insert(Symbol::intern("error"), Type::Function { params: vec![Type::Any], varargs: Some(any()), returns: vec![] });
+    globals.insert(Symbol::intern("assert"), Type::Function { params: vec![Type::Any], varargs: Some(any()), returns: vec![Type::Any] });
+    globals.insert(Symbol::intern("type"), Type::function(vec![Type::Any], vec![Type::String]));
+    globals.insert(Symbol::intern("tostring"), Type::function(vec![Type::Any], vec![Type::String]));
+    globals.insert(Symbol::intern("tonumber"), Type::Function { params: vec![Type::Any], varargs: Some(any()), returns: vec![Type::Any] });
+    globals.insert(Symbol::intern("pairs"), Type::function(vec![Type::Any], vec![Type::Any, Type::Any, Type::Any]));
+    globals.insert(Symbol::intern("ipairs"), Type::function(vec![Type::Any], vec![Type::Any, Type::Any, Type::Any]));
+    globals.insert(Symbol::intern("next"), Type::Function { params: vec![Type::Any], varargs: Some(any()), returns: vec![Type::Any, Type::Any] });
+    globals.insert(Symbol::intern("select"), Type::Function { params: vec![Type::Any], varargs: Some(any()), returns: vec![Type::Any] });
+    globals.insert(Symbol::intern("rawequal"), Type::function(vec![Type::Any, Type::Any], vec![Type::Boolean]));
+    globals.insert(Symbol::intern("rawlen"), Type::function(vec![Type::Any], vec![Type::Number]));
+    globals.insert(Symbol::intern("rawget"), Type::function(vec![Type::Any, Type::Any], vec![Type::Any]));
+    globals.insert(Symbol::intern("rawset"), Type::function(vec![Type::Any, Type::Any, Type::Any], vec![Type::Any]));
+    globals.insert(Symbol::intern("setmetatable"), Type::function(vec![Type::Any, Type::Any], vec![Type::Any]));
+    globals.insert(Symbol::intern("getmetatable"), Type::function(vec![Type::Any], vec![Type::Any]));
+    globals.insert(Symbol::intern("pcall"), Type::Function { params: vec![Type::Any], varargs: Some(any()), returns: vec![Type::Boolean, Type::Any] });
+    globals.insert(Symbol::intern("xpcall"), Type::Function { params: vec![Type::Any, Type::Any], varargs: Some(any()), returns: vec![Type::Boolean, Type::Any] });
+    globals.insert(Symbol::intern("require"), Type::function(vec![Type::String], vec![Type::Any]));
+    globals.insert(Symbol::intern("_VERSION"), Type::String);
+    for lib in ["_G", "string", "table", "math", "io", "os", "coroutine", "utf8", "debug", "package"] {
+        globals.insert(Symbol::intern(lib), Type::Any);
+    }
+    globals
+}
+
+// `__name` is set automatically
+pub const METAMETHODS: [&str; 28] = [
+    "__index", "__newindex", "__call", "__tostring", "__len", "__pairs", "__unm", "__add", "__sub", "__mul", "__div", "__mod",
+    "__pow", "__concat", "__eq", "__lt", "__le", "__idiv", "__band", "__bor", "__bxor", "__shl", "__shr", "__bnot",
+    "__close", "__gc", "__mode", "__metatable"
+];
+pub fn metamethod(op: &TokenType, unary: bool) -> Option<&'static str> {
+    match op {
+        TokenType::Sub if unary => Some("__unm"),
+        TokenType::Len => Some("__len"),
+        TokenType::Add => Some("__add"),
+        TokenType::Sub => Some("__sub"),
+        TokenType::Mul => Some("__mul"),
+        TokenType::Div => Some("__div"),
+        TokenType::Mod => Some("__mod"),
+        TokenType::Pow => Some("__pow"),
+        TokenType::Concat => Some("__concat"),
+        TokenType::IDiv => Some("__idiv"),
+        TokenType::BXor if unary => Some("__bnot"),
+        TokenType::BAnd => Some("__band"),
+        TokenType::BOr => Some("__bor"),
+        TokenType::BXor => Some("__bxor"),
+        TokenType::Shl => Some("__shl"),
+        TokenType::Shr => Some("__shr"),
+        TokenType::LT | TokenType::GT => Some("__lt"),
+        TokenType::LE | TokenType::GE => Some("__le"),
+        _ => None
+    }
+}
+
+pub struct Meta {
+    generics: Vec<Symbol>,
+    fields: HashMap<Symbol, Type>,
+    impls: HashMap<Symbol, Type>
+}
+// name, parameters, type and body of a field function
+pub type Signature = (Symbol, Vec<(Symbol, Type)>, Type, NodeId);
+// the types of a fixed number of values, followed by any number of values of the second type
+pub type Values = (Vec<Type>, Option<Type>);
+// the values passed to a call with the positions they come from
+pub type Arguments = (Vec<(Type, Position)>, Option<(Type, Position)>);
+
+// where the labels of a block are and how many locals are declared before each of its statements
+pub struct Labels {
+    names: HashMap<Symbol, usize>,
+    locals: Vec<usize>,
+    // labels from here on only have other labels after them, they're outside the scope of the block's locals
+    end: usize,
+    // the statement being checked
+    current: usize
+}
+
+pub struct Checker<'a> {
+    ast: &'a Ast,
+    scopes: Vec<HashMap<Symbol, Type>>,
+    returns: Vec<Values>,
+    // the type of `...` in every function that is being checked
+    varargs: Vec<Option<Type>>,
+    metas: HashMap<Symbol, Meta>,
+    generics: Vec<Symbol>,
+    // the blocks of every function that is being checked
+    labels: Vec<Vec<Labels>>,
+    // how many loops the current statement is in, for every function that is being checked
+    loops: Vec<usize>
+}
+impl<'a> Checker<'a> {
+    pub fn new(ast: &'a Ast) -> Self { Self { ast, scopes: vec![prelude()], returns: vec![], varargs: vec![Some(Type::Any)], metas: HashMap::new(), generics: vec![], labels: vec![vec![]], loops: vec![0] } }
+    pub fn push(&mut self) { self.scopes.push(HashMap::new()); }
+    pub fn pop(&mut self) { self.scopes.pop(); }
+    pub fn declare(&mut self, id: Symbol, typ: Type) {
+        self.scopes.last_mut().unwrap().insert(id, typ);
+    }
+    pub fn declare_global(&mut self, id: Symbol, typ: Type) {
+        self.scopes.first_mut().unwrap().insert(id, typ);
+    }
+    pub fn lookup(&self, id: Symbol) -> Option<&Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&id))
+    }
+    pub fn error(&self, kind: ErrorKind, pos: &Position) -> Error {
+        Error::new(kind, pos.clone())
+    }
+    pub fn unexpected(&self, id: NodeId) -> Error {
+        let node = self.ast.get(id);
+        self.error(ErrorKind::UnexpectedNode(node.node().name().to_string()), node.pos())
+    }
+    pub fn expect(&self, expected: &Type, got: &Type, pos: &Position) -> Result<(), Error> {
+        if !got.is_assignable_to(expected) {
+            return Err(self.error(ErrorKind::MismatchedType(expected.clone(), got.clone()), pos))
+        }
+        Ok(())
+    }
+    // a local without a value or initialized with nil can hold anything later on
+    pub fn widen(typ: Type) -> Type {
+        if typ == Type::Nil { Type::Any } else { typ }
+    }
+
+    pub fn chunk(&mut self, id: NodeId) -> Result<(), Error> {
+        let node = self.ast.get(id);
+        let NodeType::Chunk(nodes) = node.node() else {
+            return Err(self.unexpected(id))
+        };
+        // metas can be referred to before their definition
+        for &node in nodes {
+            if let NodeType::Meta { name, generics, funcs:_ } = self.ast.get(node).node() { self.meta(*name, generics); }
+        }
+        // so are the metamethods
+        for &node in nodes {
+            if let NodeType::Implement { name, generics, args:_, funcs } = self.ast.get(node).node() {
+                if !self.metas.contains_key(name) { continue }
+                self.generics = generics.clone();
+                for (method, _, typ, _) in self.signatures(funcs)? {
+                    self.metas.get_mut(name).unwrap().impls.insert(method, typ);
+                }
+                self.generics.clear();
+            }
+        }
+        // and so are global functions
+        for &node in nodes {
+            if let NodeType::Function { path, method: None, params, varargs, ret, body:_ } = self.ast.get(node).node() {
+                let [name] = path.as_slice() else { continue };
+                if self.lookup(*name).is_some() { continue }
+                let (_, typ) = self.signature(params, *varargs, ret)?;
+                self.declare_global(*name, typ);
+            }
+        }
+        self.statements(nodes, true)
+    }
+    pub fn meta(&mut self, name: Symbol, generics: &[Symbol]) {
+        self.metas.insert(name, Meta { generics: generics.to_vec(), fields: HashMap::new(), impls: HashMap::new() });
+        self.declare_global(name, Type::Meta(name));
+    }
+    pub fn block(&mut self, nodes: &[NodeId]) -> Result<(), Error> {
+        self.push();
+        let res = self.statements(nodes, true);
+        self.pop();
+        res
+    }
+    // checks statements in the current scope, `closed` is false if something after them can still see their locals
+    pub fn statements(&mut self, nodes: &[NodeId], closed: bool) -> Result<(), Error> {
+        let labels = self.labels(nodes, closed)?;
+        self.labels.last_mut().unwrap().push(labels);
+        let res = nodes.iter().enumerate().try_for_each(|(i, &node)| {
+            self.labels.last_mut().unwrap().last_mut().unwrap().current = i;
+            self.stat(node)
+        });
+        self.labels.last_mut().unwrap().pop();
+        res
+    }
+    pub fn labels(&self, nodes: &[NodeId], closed: bool) -> Result<Labels, Error> {
+        let mut labels = Labels { names: HashMap::new(), locals: vec![0], end: nodes.len(), current: 0 };
+        for (i, &id) in nodes.iter().enumerate() {
+            let node = self.ast.get(id);
+            let local = matches!(node.node(), NodeType::LocalAssign(_, _) | NodeType::LocalAssignVars(_, _) |
+            NodeType::LocalFunction { name:_, params:_, varargs:_, ret:_, body:_ });
+            labels.locals.push(labels.locals[i] + local as usize);
+            let NodeType::Label(name) = node.node() else { continue };
+            // labels of enclosing blocks that come before this one are still visible
+            let visible = self.labels.last().unwrap().iter().any(|x| x.names.get(name).is_some_and(|&i| i < x.current));
+            if labels.names.insert(*name, i).is_some() || visible {
+                return Err(self.error(ErrorKind::DuplicateLabel(name.to_string()), node.pos()))
+            }
+        }
+        if closed {
+            while labels.end > 0 && matches!(self.ast.get(nodes[labels.end - 1]).node(), NodeType::Label(_)) { labels.end -= 1; }
+        }
+        Ok(labels)
+    }
+    pub fn body(&mut self, id: NodeId) -> Result<(), Error> {
+        let node = self.ast.get(id);
+        let NodeType::Body(nodes) = node.node() else {
+            return Err(self.unexpected(id))
+        };
+        self.block(nodes)
+    }
+    // like in lua any value can be a condition, only nil and false are falsy
+    pub fn cond(&mut self, id: NodeId) -> Result<(), Error> {
+        self.expr(id).map(|_| ())
+    }
+    pub fn assign(&mut self, var: NodeId, typ: Type, pos: &Position) -> Result<(), Error> {
+        if let NodeType::ID(id) = self.ast.get(var).node() {
+            let Some(var_type) = self.lookup(*id) else {
+                self.declare_global(*id, Self::widen(typ));
+                return Ok(())
+            };
+            return self.expect(var_type, &typ, pos)
+        }
+        let var_type = self.expr(var)?;
+        self.expect(&var_type, &typ, pos)
+    }
+
+    // declares a local, `typ` is none if the local is left uninitialized
+    pub fn local(&mut self, var: NodeId, typ: Option<Type>, pos: &Position) -> Result<(), Error> {
+        let NodeType::Var { name, typ: annotation } = self.ast.get(var).node() else {
+            return Err(self.unexpected(var))
+        };
+        if let Some(annotation) = annotation {
+            let var_type = self.resolve(*annotation)?;
+            if let Some(typ) = typ { self.expect(&var_type, &typ, pos)?; }
+            self.declare(*name, var_type);
+        } else {
+            self.declare(*name, Self::widen(typ.unwrap_or(Type::Nil)));
+        }
+        Ok(())
+    }
+    pub fn resolve(&mut self, id: NodeId) -> CheckResult {
+        let node = self.ast.get(id);
+        match node.node() {
+            NodeType::TypeName { name, args } => self.resolve_name(id, *name, args),
+            NodeType::TypeFn { params, varargs, ret } => Ok(Type::Function {
+                params: params.iter().map(|&x| self.resolve(x)).collect::<Result<Vec<Type>, Error>>()?,
+                varargs: if let Some(varargs) = varargs { Some(Box::new(self.resolve(*varargs)?)) } else { None },
+                returns: if let Some(ret) = ret { self.resolve_returns(*ret)? } else { vec![] }
+            }),
+            NodeType::TypeOptional(typ) => Ok(Type::optional(self.resolve(*typ)?)),
+            NodeType::TypeUnion(types) => Ok(Type::union(types.iter().map(|&x| self.resolve(x)).collect::<Result<Vec<Type>, Error>>()?)),
+            _ => Err(self.unexpected(id))
+        }
+    }
+    pub fn resolve_returns(&mut self, id: NodeId) -> Result<Vec<Type>, Error> {
+        let node = self.ast.get(id);
+        match node.node() {
+            NodeType::TypeTuple(types) => types.iter().map(|&x| self.resolve(x)).collect(),
+            _ => Ok(vec![self.resolve(id)?])
+        }
+    }
+    pub fn resolve_name(&mut self, id: NodeId, name: Symbol, args: &[NodeId]) -> CheckResult {
+        match (name.as_str().as_ref(), args) {
+            ("nil", []) => Ok(Type::Nil),
+            ("boolean", []) => Ok(Type::Boolean),
+            ("number", []) => Ok(Type::Number),
+            ("string", []) => Ok(Type::String),
+            ("any", []) => Ok(Type::Any),
+            ("table", []) => Ok(Type::table(Type::Any, Type::Any)),
+            ("table", &[key, value]) => Ok(Type::table(self.resolve(key)?, self.resolve(value)?)),
+            ("function", []) => Ok(Type::Function { params: vec![], varargs: Some(Box::new(Type::Any)), returns: vec![Type::Any] }),
+            (_, []) if self.generics.contains(&name) => Ok(Type::Generic(name)),
+            (_, args) if self.metas.get(&name).is_some_and(|x| args.is_empty() || args.len() == x.generics.len()) => Ok(Type::Named {
+                name,
+                args: args.iter().map(|&x| self.resolve(x)).collect::<Result<Vec<Type>, Error>>()?
+            }),
+            _ => Err(self.error(ErrorKind::UnknownType(self.ast.display(id).to_string()), self.ast.pos(id)))
+        }
+    }
+    pub fn signature(&mut self, params: &[NodeId], varargs: bool, ret: &Option<NodeId>) -> Result<(Vec<(Symbol, Type)>, Type), Error> {
+        let mut vars = vec![];
+        for &param in params {
+            let NodeType::Var { name, typ } = self.ast.get(param).node() else {
+                return Err(self.unexpected(param))
+            };
+            let typ = if let Some(typ) = typ { self.resolve(*typ)? } else { Type::Any };
+            vars.push((*name, typ));
+        }
+        let returns = if let Some(ret) = ret { self.resolve_returns(*ret)? } else { vec![Type::Any] };
+        let typ = Type::Function {
+            params: vars.iter().map(|(_, typ)| typ.clone()).collect(),
+            varargs: if varargs { Some(Box::new(Type::Any)) } else { None },
+            returns
+        };
+        Ok((vars, typ))
+    }
+    pub fn signatures(&mut self, funcs: &[NodeId]) -> Result<Vec<Signature>, Error> {
+        let mut signatures = vec![];
+        for &func in funcs {
+            let NodeType::Function { path, method:_, params, varargs, ret, body } = self.ast.get(func).node() else {
+                return Err(self.unexpected(func))
+            };
+            let (vars, typ) = self.signature(params, *varargs, ret)?;
+            signatures.push((Symbol::intern(&join(path, ".")), vars, typ, *body));
+        }
+        Ok(signatures)
+    }
+    // the implementation of a metamethod for values of a meta type
+    pub fn metamethod(&self, typ: &Type, method: &str) -> Option<Type> {
+        let Type::Named { name, args:_ } = typ else { return None };
+        self.metas.get(name)?.impls.get(&Symbol::intern(method)).cloned()
+    }
+    pub fn function(&mut self, vars: Vec<(Symbol, Type)>, typ: &Type, body: NodeId) -> Result<(), Error> {
+        let Type::Function { params:_, varargs, returns } = typ else {
+            return Err(self.unexpected(body))
+        };
+        self.push();
+        for (name, typ) in vars { self.declare(name, typ); }
+        self.returns.push(Self::results(returns));
+        self.varargs.push(varargs.as_deref().cloned());
+        // labels aren't visible inside nested functions and `break` can't leave them
+        self.labels.push(vec![]);
+        self.loops.push(0);
+        let res = self.body(body);
+        self.loops.pop();
+        self.labels.pop();
+        self.varargs.pop();
+        self.returns.pop();
+        self.pop();
+        res
+    }
+    pub fn in_loop(&mut self, check: impl FnOnce(&mut Self) -> Result<(), Error>) -> Result<(), Error> {
+        *self.loops.last_mut().unwrap() += 1;
+        let res = check(self);
+        *self.loops.last_mut().unwrap() -= 1;
+        res
+    }
+    // the type shared by all elements, falls back to `any`
+    pub fn common(types: Vec<Type>) -> Type {
+        match types.split_first() {
+            Some((first, rest)) if rest.iter().all(|x| x == first) => first.clone(),
+            _ => Type::Any
+        }
+    }
+    pub fn returns(typ: &Type) -> Option<Type> {
+        match typ {
+            Type::Function { params:_, varargs:_, returns } => returns.first().cloned(),
+            _ => None
+        }
+    }
+    // functions without a declared return type and ones returning `any` can return any number of values
+    pub fn results(returns: &[Type]) -> Values {
+        match returns {
+            [Type::Any] => (vec![], Some(Type::Any)),
+            _ => (returns.to_vec(), None)
+        }
+    }
+    // the value at an index of a list, missing values are nil
+    pub fn nth(values: &Values, i: usize) -> Type {
+        values.0.get(i).or(values.1.as_ref()).cloned().unwrap_or(Type::Nil)
+    }
+
+    pub fn stat(&mut self, id: NodeId) -> Result<(), Error> {
+        let node = self.ast.get(id);
+        match node.node() {
+            NodeType::DoBlock(nodes) => self.block(nodes),
+            NodeType::Call { head:_, args:_ } | NodeType::SelfCall { head:_, field:_, args:_ } => self.expr(id).map(|_| ()),
+            NodeType::Assign(var, expr) => {
+                let typ = self.expr(*expr)?;
+                self.assign(*var, typ, self.ast.pos(*expr))
+            }
+            NodeType::AssignVars(vars, exprs) => {
+                let values = self.values(exprs)?;
+                for (i, &var) in vars.iter().enumerate() {
+                    self.assign(var, Self::nth(&values, i), self.ast.pos(*exprs.get(i).or(exprs.last()).unwrap_or(&var)))?;
+                }
+                Ok(())
+            }
+            NodeType::LocalAssign(var, expr) => {
+                let typ = if let Some(expr) = expr { Some(self.expr(*expr)?) } else { None };
+                self.local(*var, typ, self.ast.pos(expr.unwrap_or(*var)))
+            }
+            NodeType::LocalAssignVars(vars, exprs) => {
+                let values = self.values(exprs)?;
+                for (i, &var) in vars.iter().enumerate() {
+                    let typ = if exprs.is_empty() { None } else { Some(Self::nth(&values, i)) };
+                    self.local(var, typ, self.ast.pos(*exprs.get(i).or(exprs.last()).unwrap_or(&var)))?;
+                }
+                Ok(())
+            }
+            NodeType::Return(exprs) => {
+                let values = self.values(exprs)?;
+                // the main chunk can return anything
+                let Some(ret) = self.returns.last().cloned() else { return Ok(()) };
+                if ret.1.is_none() && values.0.len() > ret.0.len() {
+                    return Err(self.error(ErrorKind::ReturnCount(ret.0.len(), values.0.len()), node.pos()))
+                }
+                for i in 0..ret.0.len().max(values.0.len()) {
+                    self.expect(&Self::nth(&ret, i), &Self::nth(&values, i), self.ast.pos(*exprs.get(i).or(exprs.last()).unwrap_or(&id)))?;
+                }
+                Ok(())
+            }
+            NodeType::Break if *self.loops.last().unwrap() == 0 => Err(self.error(ErrorKind::BreakOutsideLoop, node.pos())),
+            NodeType::Break | NodeType::Label(_) => Ok(()),
+            NodeType::Goto(name) => {
+                for labels in self.labels.last().unwrap().iter().rev() {
+                    let Some(&target) = labels.names.get(name) else { continue };
+                    let from = labels.current;
+                    if target > from && target < labels.end && labels.locals[target] > labels.locals[from] {
+                        return Err(self.error(ErrorKind::JumpIntoScope(name.to_string()), node.pos()))
+                    }
+                    return Ok(())
+                }
+                Err(self.error(ErrorKind::UndefinedLabel(name.to_string()), node.pos()))
+            }
+            NodeType::If { conds, cases, else_case } => {
+                for (&cond, &case) in conds.iter().zip(cases) {
+                    self.cond(cond)?;
+                    self.body(case)?;
+                }
+                if let Some(else_case) = else_case { self.body(*else_case)?; }
+                Ok(())
+            }
+            NodeType::While { cond, body } => {
+                self.cond(*cond)?;
+                self.in_loop(|x| x.body(*body))
+            }
+            NodeType::Repeat { body, cond } => {
+                let NodeType::Body(nodes) = self.ast.get(*body).node() else {
+                    return Err(self.unexpected(*body))
+                };
+                // the condition can see the locals of the body
+                self.push();
+                let res = self.in_loop(|x| x.statements(nodes, false)).and_then(|_| self.cond(*cond));
+                self.pop();
+                res
+            }
+            NodeType::ForIn { vars, iter, body } => {
+                self.expr(*iter)?;
+                self.push();
+                for &var in vars { self.declare(var, Type::Any); }
+                let res = self.in_loop(|x| x.body(*body));
+                self.pop();
+                res
+            }
+            NodeType::For { var, start, end, step, body } => {
+                for bound in [Some(*start), Some(*end), *step].into_iter().flatten() {
+                    let typ = self.expr(bound)?;
+                    self.expect(&Type::Number, &typ, self.ast.pos(bound))?;
+                }
+                self.push();
+                self.declare(*var, Type::Number);
+                let res = self.in_loop(|x| x.body(*body));
+                self.pop();
+                res
+            }
+            NodeType::Function { path, method, params, varargs, ret, body } => {
+                let (mut vars, mut typ) = self.signature(params, *varargs, ret)?;
+                let Some((&name, fields)) = path.split_first() else {
+                    return Err(self.unexpected(id))
+                };
+                if fields.is_empty() && method.is_none() {
+                    match self.lookup(name) {
+                        Some(var_type) => self.expect(var_type, &typ, node.pos())?,
+                        None => self.declare_global(name, typ.clone())
+                    }
+                } else {
+                    let Some(mut head) = self.lookup(name).cloned() else {
+                        return Err(self.error(ErrorKind::UndefinedVariable(name.to_string()), node.pos()))
+                    };
+                    for _ in fields {
+                        head = self.index(head, Type::String, node.pos(), node.pos())?;
+                    }
+                    if method.is_some() {
+                        vars.insert(0, (Symbol::intern("self"), head.clone()));
+                        if let Type::Function { params, varargs:_, returns:_ } = &mut typ { params.insert(0, head.clone()); }
+                    }
+                    let field_type = self.index(head, Type::String, node.pos(), node.pos())?;
+                    self.expect(&field_type, &typ, node.pos())?;
+                }
+                self.function(vars, &typ, *body)
+            }
+            NodeType::Meta { name, generics, funcs } => {
+                if !self.metas.contains_key(name) { self.meta(*name, generics); }
+                self.generics = generics.clone();
+                // all signatures are known before the bodies are checked
+                let signatures = self.signatures(funcs)?;
+                for (field, _, typ, _) in signatures.iter() {
+                    self.metas.get_mut(name).unwrap().fields.insert(*field, typ.clone());
+                }
+                for (_, vars, typ, body) in signatures {
+                    self.function(vars, &typ, body)?;
+                }
+                self.generics.clear();
+                Ok(())
+            }
+            NodeType::Implement { name, generics, args, funcs } => {
+                if !self.metas.contains_key(name) {
+                    return Err(self.error(ErrorKind::UnknownType(name.to_string()), node.pos()))
+                }
+                self.generics = generics.clone();
+                for &arg in args { self.resolve(arg)?; }
+                let signatures = self.signatures(funcs)?;
+                for (method, _, typ, body) in signatures.iter() {
+                    let meta = self.metas.get_mut(name).unwrap();
+                    if !METAMETHODS.contains(&method.as_str().as_ref()) && !meta.fields.contains_key(method) {
+                        return Err(self.error(ErrorKind::UnknownMetamethod(method.to_string()), self.ast.pos(*body)))
+                    }
+                    meta.impls.insert(*method, typ.clone());
+                }
+                for (_, vars, typ, body) in signatures {
+                    self.function(vars, &typ, body)?;
+                }
+                self.generics.clear();
+                Ok(())
+            }
+            NodeType::LocalFunction { name, params, varargs, ret, body } => {
+                let (vars, typ) = self.signature(params, *varargs, ret)?;
+                self.declare(*name, typ.clone());
+                self.function(vars, &typ, *body)
+            }
+            _ => Err(self.unexpected(id))
+        }
+    }
+
+    pub fn expr(&mut self, id: NodeId) -> CheckResult {
+        let node = self.ast.get(id);
+        match node.node() {
+            NodeType::ID(id) => match self.lookup(*id) {
+                Some(typ) => Ok(typ.clone()),
+                None => Err(self.error(ErrorKind::UndefinedVariable(id.to_string()), node.pos()))
+            }
+            NodeType::Integer(_) | NodeType::Float(_) => Ok(Type::Number),
+            NodeType::Boolean(_) => Ok(Type::Boolean),
+            NodeType::String(_) => Ok(Type::String),
+            NodeType::Nil => Ok(Type::Nil),
+            NodeType::Expr(n) => self.expr(*n),
+            NodeType::Binary { left, op, right } => {
+                let (left_type, right_type) = (self.expr(*left)?, self.expr(*right)?);
+                self.binary(op, left_type, right_type, self.ast.pos(*left), self.ast.pos(*right))
+            }
+            NodeType::Unary { op, node: operand } => {
+                let typ = self.expr(*operand)?;
+                if let Some(func) = metamethod(op, true).and_then(|x| self.metamethod(&typ, x)) {
+                    return Ok(Self::returns(&func).unwrap_or(Type::Nil))
+                }
+                match op {
+                    TokenType::Not => Ok(Type::Boolean),
+                    TokenType::Sub | TokenType::BXor if typ.is_assignable_to(&Type::Number) => Ok(Type::Number),
+                    TokenType::Len if matches!(typ, Type::Any | Type::String | Type::Table(_, _)) => Ok(Type::Number),
+                    _ => Err(self.error(ErrorKind::InvalidOperand(op.clone(), typ), self.ast.pos(*operand)))
+                }
+            }
+            NodeType::Field { left, right, expr } => {
+                let head = self.expr(*left)?;
+                let (left, right) = (self.ast.get(*left), self.ast.get(*right));
+                if let (Type::Meta(name), NodeType::ID(field), false) = (&head, right.node(), expr) {
+                    return match self.metas.get(name).and_then(|x| x.fields.get(field)) {
+                        Some(typ) => Ok(typ.clone()),
+                        None => Err(self.error(ErrorKind::UndefinedField(head.clone(), field.to_string()), right.pos()))
+                    }
+                }
+                let key = match (right.node(), expr) {
+                    (NodeType::ID(_), false) => Type::String,
+                    _ => self.expr(right.id())?
+                };
+                self.index(head, key, left.pos(), right.pos())
+            }
+            // calls and `...` are truncated to their first value
+            NodeType::Call { head:_, args:_ } | NodeType::SelfCall { head:_, field:_, args:_ } | NodeType::Varargs => Ok(Self::nth(&self.multi(id)?, 0)),
+            NodeType::Table(fields) => {
+                let (mut keys, mut values) = (vec![], vec![]);
+                for field in fields {
+                    let (key, value) = match *field {
+                        TableField::Item(value) => (Type::Number, value),
+                        TableField::Named(_, value) => (Type::String, value),
+                        TableField::Keyed(key, value) => (self.expr(key)?, value),
+                    };
+                    keys.push(key);
+                    values.push(self.expr(value)?);
+                }
+                Ok(Type::table(Self::common(keys), Self::common(values)))
+            }
+            NodeType::Lambda { params, varargs, ret, body } => {
+                let (vars, typ) = self.signature(params, *varargs, ret)?;
+                self.function(vars, &typ, *body)?;
+                Ok(typ)
+            }
+            _ => Err(self.unexpected(id))
+        }
+    }
+    pub fn binary(&mut self, op: &TokenType, left: Type, right: Type, left_pos: &Position, right_pos: &Position) -> CheckResult {
+        if let Some(method) = metamethod(op, false) {
+            if let Some(func) = self.metamethod(&left, method).or_else(|| self.metamethod(&right, method)) {
+                if let TokenType::LT | TokenType::GT | TokenType::LE | TokenType::GE = op { return Ok(Type::Boolean) }
+                return Ok(Self::returns(&func).unwrap_or(Type::Nil))
+            }
+        }
+        match op {
+            TokenType::Add | TokenType::Sub | TokenType::Mul | TokenType::Div | TokenType::Mod | TokenType::Pow | TokenType::IDiv |
+            TokenType::BAnd | TokenType::BOr | TokenType::BXor | TokenType::Shl | TokenType::Shr => {
+                if !left.is_assignable_to(&Type::Number) { return Err(self.error(ErrorKind::InvalidOperand(op.clone(), left), left_pos)) }
+                if !right.is_assignable_to(&Type::Number) { return Err(self.error(ErrorKind::InvalidOperand(op.clone(), right), right_pos)) }
+                if left == Type::Any || right == Type::Any { return Ok(Type::Any) }
+                Ok(Type::Number)
+            }
+            TokenType::Concat => {
+                for (typ, pos) in [(left, left_pos), (right, right_pos)] {
+                    if !matches!(typ, Type::Any | Type::String | Type::Number) { return Err(self.error(ErrorKind::InvalidOperand(op.clone(), typ), pos)) }
+                }
+                Ok(Type::String)
+            }
+            TokenType::EQ | TokenType::NE => Ok(Type::Boolean),
+            TokenType::LT | TokenType::GT | TokenType::LE | TokenType::GE => match (&left, &right) {
+                (Type::Any, _) | (_, Type::Any) | (Type::Number, Type::Number) | (Type::String, Type::String) => Ok(Type::Boolean),
+                (Type::Number | Type::String, _) => Err(self.error(ErrorKind::MismatchedType(left, right), right_pos)),
+                _ => Err(self.error(ErrorKind::InvalidOperand(op.clone(), left), left_pos))
+            }
+            TokenType::And | TokenType::Or => if left == right { Ok(left) } else { Ok(Type::Any) }
+            _ => Err(self.error(ErrorKind::InvalidOperand(op.clone(), left), left_pos))
+        }
+    }
+    pub fn index(&mut self, head: Type, key: Type, head_pos: &Position, key_pos: &Position) -> CheckResult {
+        match head {
+            Type::Any | Type::String | Type::Named { name:_, args:_ } | Type::Meta(_) => Ok(Type::Any),
+            Type::Table(key_type, value_type) => {
+                self.expect(&key_type, &key, key_pos)?;
+                Ok(*value_type)
+            }
+            _ => Err(self.error(ErrorKind::NotIndexable(head), head_pos))
+        }
+    }
+    // all values of an expression
+    pub fn multi(&mut self, id: NodeId) -> Result<Values, Error> {
+        let node = self.ast.get(id);
+        match node.node() {
+            NodeType::Call { head, args } => {
+                let callee = self.expr(*head)?;
+                let args = self.arguments(args)?;
+                self.call(callee, args, node.pos())
+            }
+            NodeType::SelfCall { head, field:_, args } => {
+                let head_type = self.expr(*head)?;
+                let callee = self.index(head_type.clone(), Type::String, self.ast.pos(*head), node.pos())?;
+                let mut args = self.arguments(args)?;
+                args.0.insert(0, (head_type, self.ast.pos(*head).clone()));
+                self.call(callee, args, node.pos())
+            }
+            NodeType::Varargs => match self.varargs.last() {
+                Some(Some(typ)) => Ok((vec![], Some(typ.clone()))),
+                _ => Err(self.error(ErrorKind::UnexpectedVarargs, node.pos()))
+            }
+            _ => Ok((vec![self.expr(id)?], None))
+        }
+    }
+    // the values of an expression list, where only the last expression can give more or less than one value
+    pub fn values(&mut self, exprs: &[NodeId]) -> Result<Values, Error> {
+        let Some((&last, exprs)) = exprs.split_last() else { return Ok((vec![], None)) };
+        let mut types = exprs.iter().map(|&x| self.expr(x)).collect::<Result<Vec<Type>, Error>>()?;
+        let (last_types, rest) = self.multi(last)?;
+        types.extend(last_types);
+        Ok((types, rest))
+    }
+    pub fn arguments(&mut self, exprs: &[NodeId]) -> Result<Arguments, Error> {
+        let (types, rest) = self.values(exprs)?;
+        // values the last expression expands to all come from its position
+        let pos = |i: usize| self.ast.pos(exprs[i.min(exprs.len() - 1)]).clone();
+        let rest = rest.map(|typ| (typ, pos(exprs.len() - 1)));
+        Ok((types.into_iter().enumerate().map(|(i, typ)| (typ, pos(i))).collect(), rest))
+    }
+    // binds the generics of a function to the arguments they line up with, the ones no argument decides can be anything
+    pub fn instantiate(callee: Type, args: &[(Type, Position)], rest: &Option<(Type, Position)>) -> Type {
+        let Type::Function { params, varargs, returns:_ } = &callee else { return callee };
+        let mut generics = HashMap::new();
+        for (i, param) in params.iter().enumerate() {
+            if let Some((arg, _)) = args.get(i).or(rest.as_ref()) { param.infer(arg, &mut generics); }
+        }
+        if let Some(varargs) = varargs {
+            for (arg, _) in args.iter().skip(params.len()).chain(rest) { varargs.infer(arg, &mut generics); }
+        }
+        for name in callee.generics() { generics.entry(name).or_insert(Type::Any); }
+        callee.substitute(&generics)
+    }
+    pub fn call(&mut self, callee: Type, (args, rest): Arguments, pos: &Position) -> Result<Values, Error> {
+        match Self::instantiate(callee, &args, &rest) {
+            Type::Any => Ok((vec![], Some(Type::Any))),
+            // `Meta(value)` turns a table into a value of the meta type
+            Type::Meta(name) => {
+                let [(arg, arg_pos)] = args.as_slice() else {
+                    return Err(self.error(ErrorKind::ArgumentCount(1, args.len()), pos))
+                };
+                self.expect(&Type::table(Type::Any, Type::Any), arg, arg_pos)?;
+                Ok((vec![Type::Named { name, args: vec![] }], None))
+            }
+            Type::Function { params, varargs, returns } => {
+                if args.len() > params.len() && varargs.is_none() {
+                    return Err(self.error(ErrorKind::ArgumentCount(params.len(), args.len()), pos))
+                }
+                for (i, param) in params.iter().enumerate() {
+                    match args.get(i).or(rest.as_ref()) {
+                        Some((arg, arg_pos)) => self.expect(param, arg, arg_pos)?,
+                        None if Type::Nil.is_assignable_to(param) => {}
+                        None => return Err(self.error(ErrorKind::ArgumentCount(params.len(), args.len()), pos))
+                    }
+                }
+                if let Some(varargs) = varargs {
+                    for (arg, arg_pos) in args.iter().skip(params.len()).chain(&rest) {
+                        self.expect(&varargs, arg, arg_pos)?;
+                    }
+                }
+                Ok(Self::results(&returns))
+            }
+            callee => Err(self.error(ErrorKind::NotCallable(callee), pos))
+        }
+    }
+}
+
+pub fn check(ast: &Ast) -> Result<(), Error> {
+    Checker::new(ast).chunk(ast.root())
+}
\ No newline at end of file
@@ -0,0 +1,435 @@
+use std::fmt::Display;
+use crate::error::{Error, ErrorKind};
+use crate::scanning::{nodes::{Ast, NodeId, NodeType, TableField, join}, tokens::{TokenType, UNARY_PRIORITY}, symbol::Symbol};
+
+pub type GenResult = Result<String, Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target { Lua51, Lua52, Lua53, Lua54, LuaJIT }
+impl Target {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "5.1" => Some(Self::Lua51),
+            "5.2" => Some(Self::Lua52),
+            "5.3" => Some(Self::Lua53),
+            "5.4" => Some(Self::Lua54),
+            "jit" | "luajit" => Some(Self::LuaJIT),
+            _ => None
+        }
+    }
+    // the library bitwise operations are lowered to on versions without the operators
+    pub fn bit_library(&self) -> Option<&'static str> {
+        match self {
+            Self::Lua51 | Self::LuaJIT => Some("bit"),
+            Self::Lua52 => Some("bit32"),
+            Self::Lua53 | Self::Lua54 => None
+        }
+    }
+    // lua 5.1 and luajit don't allow `;` on its own
+    pub fn empty_statement(&self) -> bool {
+        !matches!(self, Self::Lua51 | Self::LuaJIT)
+    }
+}
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lua51 => write!(f, "Lua 5.1"),
+            Self::Lua52 => write!(f, "Lua 5.2"),
+            Self::Lua53 => write!(f, "Lua 5.3"),
+            Self::Lua54 => write!(f, "Lua 5.4"),
+            Self::LuaJIT => write!(f, "LuaJIT"),
+        }
+    }
+}
+pub fn bit_function(op: &TokenType, unary: bool) -> Option<&'static str> {
+    match op {
+        TokenType::BXor if unary => Some("bnot"),
+        TokenType::BAnd => Some("band"),
+        TokenType::BOr => Some("bor"),
+        TokenType::BXor => Some("bxor"),
+        TokenType::Shl => Some("lshift"),
+        TokenType::Shr => Some("rshift"),
+        _ => None
+    }
+}
+
+// only wrapped hexadecimal literals are negative, they shouldn't merge with a preceding `-`
+pub fn integer(v: i64) -> String {
+    // the smallest integer has no positive counterpart, the numeral for it would be read as a float
+    if v == i64::MIN { return format!("(-{} - 1)", i64::MAX) }
+    if v < 0 { return format!("(-{})", -v) }
+    v.to_string()
+}
+// floats keep a fraction or an exponent so they stay floats in lua 5.3 and later
+pub fn float(v: f64) -> String {
+    if v.is_nan() { return "(0/0)".to_string() }
+    if v < 0.0 { return format!("(-{})", float(-v)) }
+    if v.is_infinite() { return "math.huge".to_string() }
+    format!("{v:?}")
+}
+pub fn string(v: &[u8]) -> String {
+    let mut string = String::from('"');
+    for chunk in v.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => string.push_str("\\\""),
+                '\\' => string.push_str("\\\\"),
+                '\n' => string.push_str("\\n"),
+                '\r' => string.push_str("\\r"),
+                '\t' => string.push_str("\\t"),
+                _ if c.is_ascii_control() => string.push_str(format!("\\{:03}", c as u32).as_str()),
+                _ => string.push(c)
+            }
+        }
+        // bytes that aren't utf-8 can't be written into the file as they are
+        for byte in chunk.invalid() { string.push_str(format!("\\{byte:03}").as_str()); }
+    }
+    string.push('"');
+    string
+}
+
+pub const IMPLS: &str = "__IMPLS";
+
+pub struct Generator<'a> {
+    ast: &'a Ast,
+    target: Target,
+    indent: usize,
+    metas: Vec<Symbol>,
+    // the locals of every block being generated, they can shadow metas
+    locals: Vec<Vec<Symbol>>
+}
+impl<'a> Generator<'a> {
+    pub fn new(ast: &'a Ast, target: Target) -> Self { Self { ast, target, indent: 0, metas: vec![], locals: vec![vec![]] } }
+    pub fn prefix(&self) -> String { "    ".repeat(self.indent) }
+    pub fn declare(&mut self, names: &[Symbol]) { self.locals.last_mut().unwrap().extend(names); }
+    // generates code in a new scope holding `names`
+    pub fn scoped(&mut self, names: &[Symbol], generate: impl FnOnce(&mut Self) -> GenResult) -> GenResult {
+        self.locals.push(names.to_vec());
+        let res = generate(self);
+        self.locals.pop();
+        res
+    }
+    // a name refers to a meta unless a local hides it
+    pub fn is_meta(&self, name: Symbol) -> bool {
+        self.metas.contains(&name) && !self.locals.iter().any(|x| x.contains(&name))
+    }
+    pub fn unexpected(&self, id: NodeId) -> Error {
+        let node = self.ast.get(id);
+        Error::new(ErrorKind::UnexpectedNode(node.node().name().to_string()), node.pos().clone())
+    }
+
+    pub fn chunk(&mut self, id: NodeId) -> GenResult {
+        let node = self.ast.get(id);
+        let NodeType::Chunk(nodes) = node.node() else {
+            return Err(self.unexpected(id))
+        };
+        // every meta gets an entry in the registry, implementations are merged into it
+        let mut impls: Vec<(Symbol, Vec<NodeId>)> = vec![];
+        for &node in nodes {
+            let (name, funcs) = match self.ast.get(node).node() {
+                NodeType::Meta { name, generics:_, funcs:_ } => {
+                    self.metas.push(*name);
+                    (*name, vec![])
+                }
+                NodeType::Implement { name, generics:_, args:_, funcs } => (*name, funcs.clone()),
+                _ => continue
+            };
+            match impls.iter_mut().find(|(impl_name, _)| *impl_name == name) {
+                Some((_, impl_funcs)) => impl_funcs.extend(funcs),
+                None => impls.push((name, funcs))
+            }
+        }
+        let mut stats = vec![];
+        if !impls.is_empty() { stats.push(self.impls(impls)?); }
+        for &node in nodes {
+            if let NodeType::Implement { name:_, generics:_, args:_, funcs:_ } = self.ast.get(node).node() { continue }
+            stats.push(self.stat(node)?);
+        }
+        self.separate(&mut stats);
+        Ok(stats.join("\n"))
+    }
+    // the registry of the metatables the meta values get
+    pub fn impls(&mut self, impls: Vec<(Symbol, Vec<NodeId>)>) -> GenResult {
+        self.indent += 1;
+        let prefix = self.prefix();
+        let mut entries = vec![];
+        for (name, funcs) in impls {
+            let fields = self.fields(&funcs);
+            entries.push(format!("{prefix}{name} = {{\n{prefix}    __name = {},{}\n{prefix}}}", string(name.as_bytes()), fields?));
+        }
+        self.indent -= 1;
+        Ok(format!("{IMPLS} = {{\n{}\n}}", entries.join(",\n")))
+    }
+    // `name = function(...) ... end` inside of a table
+    pub fn field_function(&mut self, id: NodeId) -> GenResult {
+        let node = self.ast.get(id);
+        let NodeType::Function { path, method: None, params, varargs, ret:_, body } = node.node() else {
+            return Err(self.unexpected(id))
+        };
+        let [name] = path.as_slice() else {
+            return Err(self.unexpected(id))
+        };
+        let prefix = self.prefix();
+        Ok(format!("{prefix}{name} = function({}){}\n{prefix}end", self.params(params, *varargs)?, self.function_body(params, &[], *body)?))
+    }
+    pub fn fields(&mut self, funcs: &[NodeId]) -> GenResult {
+        self.indent += 1;
+        let fields = funcs.iter().map(|&x| self.field_function(x)).collect::<Result<Vec<String>, Error>>();
+        self.indent -= 1;
+        Ok(fields?.iter().map(|x| format!("\n{x},")).collect())
+    }
+    pub fn block(&mut self, nodes: &[NodeId]) -> GenResult {
+        self.scoped(&[], |x| x.statements(nodes))
+    }
+    // statements in the current scope
+    pub fn statements(&mut self, nodes: &[NodeId]) -> GenResult {
+        self.indent += 1;
+        let stats = nodes.iter().map(|&x| self.stat(x)).collect::<Result<Vec<String>, Error>>();
+        self.indent -= 1;
+        let mut stats = stats?;
+        self.separate(&mut stats);
+        Ok(stats.iter().map(|x| format!("\n{x}")).collect())
+    }
+    // keeps statements starting with '(' from being read as a call continuing the previous statement
+    pub fn separate(&self, stats: &mut [String]) {
+        for i in 1..stats.len() {
+            let indent = stats[i].len() - stats[i].trim_start().len();
+            if !stats[i][indent..].starts_with('(') { continue }
+            if self.target.empty_statement() { stats[i].insert(indent, ';') } else { stats[i - 1].push(';') }
+        }
+    }
+    pub fn body(&mut self, id: NodeId) -> GenResult {
+        let node = self.ast.get(id);
+        let NodeType::Body(nodes) = node.node() else {
+            return Err(self.unexpected(id))
+        };
+        self.block(nodes)
+    }
+    // the parameters are locals of the body, methods also get `self`
+    pub fn function_body(&mut self, params: &[NodeId], implicit: &[Symbol], body: NodeId) -> GenResult {
+        let mut names = implicit.to_vec();
+        for &param in params {
+            if let NodeType::Var { name, typ:_ } = self.ast.get(param).node() { names.push(*name) }
+        }
+        self.scoped(&names, |x| x.body(body))
+    }
+    // declared variables lose their type annotation
+    pub fn name(&mut self, id: NodeId) -> GenResult {
+        let node = self.ast.get(id);
+        let NodeType::Var { name, typ:_ } = node.node() else {
+            return Err(self.unexpected(id))
+        };
+        Ok(name.to_string())
+    }
+    pub fn declare_vars(&mut self, vars: &[NodeId]) {
+        for &var in vars {
+            if let NodeType::Var { name, typ:_ } = self.ast.get(var).node() { self.declare(&[*name]) }
+        }
+    }
+    pub fn params(&mut self, params: &[NodeId], varargs: bool) -> GenResult {
+        let mut names = params.iter().map(|&x| self.name(x)).collect::<Result<Vec<String>, Error>>()?;
+        if varargs { names.push("...".to_string()) }
+        Ok(names.join(", "))
+    }
+    pub fn exprs(&mut self, nodes: &[NodeId]) -> GenResult {
+        Ok(nodes.iter().map(|&x| self.expr(x)).collect::<Result<Vec<String>, Error>>()?.join(", "))
+    }
+    pub fn args(&mut self, args: &[NodeId]) -> GenResult {
+        match *args {
+            // keep the `f{...}` sugar for a single table argument
+            [arg] if matches!(self.ast.get(arg).node(), NodeType::Table(_)) => self.expr(arg),
+            _ => Ok(format!("({})", self.exprs(args)?))
+        }
+    }
+    // a statement that fits on the same line as its `if`
+    pub fn inline_stat(&mut self, id: NodeId) -> Result<Option<String>, Error> {
+        let node = self.ast.get(id);
+        match node.node() {
+            NodeType::Assign(_, _) | NodeType::AssignVars(_, _) | NodeType::LocalAssign(_, _) | NodeType::LocalAssignVars(_, _) |
+            NodeType::Call { head:_, args:_ } | NodeType::SelfCall { head:_, field:_, args:_ } | NodeType::Return(_) | NodeType::Break | NodeType::Goto(_) => {
+                let indent = self.indent;
+                self.indent = 0;
+                let stat = self.stat(id);
+                self.indent = indent;
+                Ok(Some(stat?))
+            }
+            _ => Ok(None)
+        }
+    }
+
+    pub fn stat(&mut self, id: NodeId) -> GenResult {
+        let node = self.ast.get(id);
+        let prefix = self.prefix();
+        match node.node() {
+            NodeType::DoBlock(nodes) => Ok(format!("{prefix}do{}\n{prefix}end", self.block(nodes)?)),
+            NodeType::Call { head:_, args:_ } | NodeType::SelfCall { head:_, field:_, args:_ } => Ok(format!("{prefix}{}", self.expr(id)?)),
+            NodeType::Assign(var, expr) => Ok(format!("{prefix}{} = {}", self.expr(*var)?, self.expr(*expr)?)),
+            NodeType::AssignVars(vars, exprs) => Ok(format!("{prefix}{} = {}", self.exprs(vars)?, self.exprs(exprs)?)),
+            // the locals are only in scope after their values
+            NodeType::LocalAssign(var, expr) => {
+                let code = format!("{prefix}local {}{}", self.name(*var)?,
+                if let Some(expr) = expr { format!(" = {}", self.expr(*expr)?) } else { String::new() });
+                self.declare_vars(&[*var]);
+                Ok(code)
+            }
+            NodeType::LocalAssignVars(vars, exprs) => {
+                let code = format!("{prefix}local {}{}",
+                vars.iter().map(|&x| self.name(x)).collect::<Result<Vec<String>, Error>>()?.join(", "),
+                if exprs.is_empty() { String::new() } else { format!(" = {}", self.exprs(exprs)?) });
+                self.declare_vars(vars);
+                Ok(code)
+            }
+            NodeType::Return(exprs) if exprs.is_empty() => Ok(format!("{prefix}return")),
+            NodeType::Return(exprs) => Ok(format!("{prefix}return {}", self.exprs(exprs)?)),
+            NodeType::Break => Ok(format!("{prefix}break")),
+            NodeType::Goto(_) | NodeType::Label(_) if self.target == Target::Lua51 =>
+                Err(Error::new(ErrorKind::Unsupported(node.node().name().to_string(), self.target.to_string()), node.pos().clone())),
+            NodeType::Goto(name) => Ok(format!("{prefix}goto {name}")),
+            NodeType::Label(name) => Ok(format!("{prefix}::{name}::")),
+            NodeType::If { conds, cases, else_case } => {
+                if let (&[cond], &[case], None) = (conds.as_slice(), cases.as_slice(), else_case) {
+                    if let NodeType::Body(nodes) = self.ast.get(case).node() {
+                        if let &[stat] = nodes.as_slice() {
+                            if let Some(stat) = self.inline_stat(stat)? {
+                                return Ok(format!("{prefix}if {} then {stat} end", self.expr(cond)?))
+                            }
+                        }
+                    }
+                }
+                let mut code = String::new();
+                for (i, (&cond, &case)) in conds.iter().zip(cases).enumerate() {
+                    code.push_str(format!("{prefix}{} {} then{}\n", if i == 0 { "if" } else { "elseif" },
+                    self.expr(cond)?, self.body(case)?).as_str());
+                }
+                if let Some(else_case) = else_case {
+                    code.push_str(format!("{prefix}else{}\n", self.body(*else_case)?).as_str());
+                }
+                code.push_str(format!("{prefix}end").as_str());
+                Ok(code)
+            }
+            NodeType::While { cond, body } => Ok(format!("{prefix}while {} do{}\n{prefix}end",
+            self.expr(*cond)?, self.body(*body)?)),
+            NodeType::Repeat { body, cond } => {
+                let NodeType::Body(nodes) = self.ast.get(*body).node() else {
+                    return Err(self.unexpected(*body))
+                };
+                // the condition can see the locals of the body
+                self.scoped(&[], |x| Ok(format!("{prefix}repeat{}\n{prefix}until {}", x.statements(nodes)?, x.expr(*cond)?)))
+            }
+            NodeType::ForIn { vars, iter, body } => Ok(format!("{prefix}for {} in {} do{}\n{prefix}end",
+            join(vars, ", "), self.expr(*iter)?, self.scoped(vars, |x| x.body(*body))?)),
+            NodeType::For { var, start, end, step, body } => Ok(format!("{prefix}for {var} = {}, {}{} do{}\n{prefix}end",
+            self.expr(*start)?, self.expr(*end)?,
+            if let Some(step) = step { format!(", {}", self.expr(*step)?) } else { String::new() },
+            self.scoped(&[*var], |x| x.body(*body))?)),
+            NodeType::Function { path, method, params, varargs, ret:_, body } => {
+                let implicit = if method.is_some() { vec![Symbol::intern("self")] } else { vec![] };
+                Ok(format!("{prefix}function {}{}({}){}\n{prefix}end",
+                join(path, "."), if let Some(method) = method { format!(":{method}") } else { String::new() },
+                self.params(params, *varargs)?, self.function_body(params, &implicit, *body)?))
+            }
+            NodeType::LocalFunction { name, params, varargs, ret:_, body } => {
+                // the function can refer to itself
+                self.declare(&[*name]);
+                Ok(format!("{prefix}local function {name}({}){}\n{prefix}end", self.params(params, *varargs)?, self.function_body(params, &[], *body)?))
+            }
+            NodeType::Meta { name, generics:_, funcs } => Ok(format!("{prefix}{name} = {{{}\n{prefix}}}", self.fields(funcs)?)),
+            _ => Err(self.unexpected(id))
+        }
+    }
+
+    pub fn expr(&mut self, id: NodeId) -> GenResult {
+        let node = self.ast.get(id);
+        match node.node() {
+            NodeType::ID(v) => Ok(v.to_string()),
+            NodeType::Integer(v) => Ok(integer(*v)),
+            NodeType::Float(v) => Ok(float(*v)),
+            NodeType::Boolean(v) => Ok(v.to_string()),
+            NodeType::String(v) => Ok(string(v.as_bytes())),
+            NodeType::Nil => Ok("nil".to_string()),
+            NodeType::Varargs => Ok("...".to_string()),
+            NodeType::Expr(n) => Ok(format!("({})", self.expr(*n)?)),
+            NodeType::Binary { left, op, right } => {
+                if let (Some(library), Some(func)) = (self.target.bit_library(), bit_function(op, false)) {
+                    return Ok(format!("{library}.{func}({}, {})", self.expr(*left)?, self.expr(*right)?))
+                }
+                if *op == TokenType::IDiv && self.target.bit_library().is_some() {
+                    return Ok(format!("math.floor({})", self.binary(*left, &TokenType::Div, *right)?))
+                }
+                self.binary(*left, op, *right)
+            }
+            NodeType::Unary { op, node } => {
+                if let (Some(library), Some(func)) = (self.target.bit_library(), bit_function(op, true)) {
+                    return Ok(format!("{library}.{func}({})", self.expr(*node)?))
+                }
+                let mut code = self.expr(*node)?;
+                if let NodeType::Binary { left:_, op, right:_ } = self.ast.get(*node).node() {
+                    if op.binary_priority().0 <= UNARY_PRIORITY { code = format!("({code})") }
+                }
+                match op {
+                    TokenType::Not => Ok(format!("not {code}")),
+                    // `- -x` must not turn into a comment
+                    _ if code.starts_with('-') => Ok(format!("{} {code}", op.display())),
+                    _ => Ok(format!("{}{code}", op.display()))
+                }
+            }
+            NodeType::Field { left, right, expr } => {
+                let head = self.prefix_expr(*left)?;
+                match self.ast.get(*right).node() {
+                    NodeType::ID(id) if !expr => Ok(format!("{head}.{id}")),
+                    _ => Ok(format!("{head}[{}]", self.expr(*right)?))
+                }
+            }
+            NodeType::Call { head, args } => {
+                // `Meta(value)` gives the value the metatable of the meta
+                if let (NodeType::ID(id), &[arg]) = (self.ast.get(*head).node(), args.as_slice()) {
+                    if self.is_meta(*id) { return Ok(format!("setmetatable({}, {IMPLS}.{id})", self.expr(arg)?)) }
+                }
+                Ok(format!("{}{}", self.prefix_expr(*head)?, self.args(args)?))
+            }
+            NodeType::Table(fields) => {
+                let mut codes = vec![];
+                for field in fields {
+                    codes.push(match *field {
+                        TableField::Item(value) => self.expr(value)?,
+                        TableField::Named(ref name, value) => format!("{name} = {}", self.expr(value)?),
+                        TableField::Keyed(key, value) => format!("[{}] = {}", self.expr(key)?, self.expr(value)?),
+                    });
+                }
+                Ok(format!("{{{}}}", codes.join(", ")))
+            }
+            NodeType::SelfCall { head, field, args } => Ok(format!("{}:{field}{}", self.prefix_expr(*head)?, self.args(args)?)),
+            NodeType::Lambda { params, varargs, ret:_, body } => Ok(format!("function({}){}\n{}end",
+            self.params(params, *varargs)?, self.function_body(params, &[], *body)?, self.prefix())),
+            _ => Err(self.unexpected(id))
+        }
+    }
+    pub fn binary(&mut self, left: NodeId, op: &TokenType, right: NodeId) -> GenResult {
+        let (left_priority, right_priority) = op.binary_priority();
+        let mut left_code = self.expr(left)?;
+        match self.ast.get(left).node() {
+            NodeType::Binary { left:_, op, right:_ } if left_priority > op.binary_priority().1 => left_code = format!("({left_code})"),
+            NodeType::Unary { op:_, node:_ } if left_priority > UNARY_PRIORITY => left_code = format!("({left_code})"),
+            _ => {}
+        }
+        let mut right_code = self.expr(right)?;
+        if let NodeType::Binary { left:_, op, right:_ } = self.ast.get(right).node() {
+            if op.binary_priority().0 <= right_priority { right_code = format!("({right_code})") }
+        }
+        Ok(format!("{left_code} {} {right_code}", op.display()))
+    }
+    // expressions that can be called or indexed without parentheses
+    pub fn prefix_expr(&mut self, id: NodeId) -> GenResult {
+        let node = self.ast.get(id);
+        let code = self.expr(id)?;
+        match node.node() {
+            NodeType::ID(_) | NodeType::Expr(_) | NodeType::Field { left:_, right:_, expr:_ } |
+            NodeType::Call { head:_, args:_ } | NodeType::SelfCall { head:_, field:_, args:_ } => Ok(code),
+            _ => Ok(format!("({code})"))
+        }
+    }
+}
+
+pub fn generate(ast: &Ast, target: Target) -> GenResult {
+    Generator::new(ast, target).chunk(ast.root())
+}
\ No newline at end of file
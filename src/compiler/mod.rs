@@ -1,2 +1,3 @@
 pub mod check;
-pub mod types;
\ No newline at end of file
+pub mod types;
+pub mod codegen;
\ No newline at end of file
@@ -0,0 +1,146 @@
+use std::{fmt::Display, collections::HashMap};
+use crate::scanning::{nodes::join, symbol::Symbol};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Nil, Boolean, Number, String, Any,
+    Table(Box<Type>, Box<Type>),
+    Function { params: Vec<Type>, varargs: Option<Box<Type>>, returns: Vec<Type> },
+    Named { name: Symbol, args: Vec<Type> },
+    Generic(Symbol),
+    Union(Vec<Type>),
+    // the value a `meta` definition is bound to
+    Meta(Symbol),
+}
+impl Type {
+    pub fn table(key: Type, value: Type) -> Self { Self::Table(Box::new(key), Box::new(value)) }
+    pub fn function(params: Vec<Type>, returns: Vec<Type>) -> Self {
+        Self::Function { params, varargs: None, returns }
+    }
+    pub fn optional(typ: Type) -> Self { Self::union(vec![typ, Self::Nil]) }
+    // flattens nested unions and drops duplicates
+    pub fn union(types: Vec<Type>) -> Self {
+        let mut flat: Vec<Type> = vec![];
+        for typ in types {
+            let members = if let Self::Union(members) = typ { members } else { vec![typ] };
+            for member in members {
+                if !flat.contains(&member) { flat.push(member) }
+            }
+        }
+        if flat.len() == 1 { return flat.pop().unwrap() }
+        Self::Union(flat)
+    }
+    pub fn is_assignable_to(&self, other: &Type) -> bool {
+        if self == other { return true }
+        match (self, other) {
+            (Self::Any, _) | (_, Self::Any) => true,
+            (Self::Union(types), _) => types.iter().all(|x| x.is_assignable_to(other)),
+            (_, Self::Union(types)) => types.iter().any(|x| self.is_assignable_to(x)),
+            (Self::Table(k1, v1), Self::Table(k2, v2)) =>
+                k1.is_assignable_to(k2) && k2.is_assignable_to(k1) && v1.is_assignable_to(v2) && v2.is_assignable_to(v1),
+            (Self::Function { params: p1, varargs: va1, returns: r1 }, Self::Function { params: p2, varargs: va2, returns: r2 }) => {
+                // parameters are contravariant: every argument the other accepts has to be accepted here
+                for (i, param) in p2.iter().enumerate() {
+                    let accepted = match (p1.get(i), va1) {
+                        (Some(typ), _) => param.is_assignable_to(typ),
+                        (None, Some(typ)) => param.is_assignable_to(typ),
+                        // extra arguments are dropped
+                        (None, None) => true,
+                    };
+                    if !accepted { return false }
+                }
+                if let Some(va2) = va2 {
+                    for param in p1.iter().skip(p2.len()) {
+                        if !va2.is_assignable_to(param) { return false }
+                    }
+                    if let Some(va1) = va1 {
+                        if !va2.is_assignable_to(va1) { return false }
+                    }
+                } else if p1.iter().skip(p2.len()).any(|x| !Self::Nil.is_assignable_to(x)) {
+                    return false
+                }
+                // return values are covariant, missing values are nil
+                r2.iter().enumerate().all(|(i, ret)| r1.get(i).unwrap_or(&Self::Nil).is_assignable_to(ret))
+            }
+            (Self::Named { name: n1, args: a1 }, Self::Named { name: n2, args: a2 }) => n1 == n2 &&
+                (a1.is_empty() || a2.is_empty() || (a1.len() == a2.len() &&
+                a1.iter().zip(a2).all(|(t1, t2)| t1.is_assignable_to(t2) && t2.is_assignable_to(t1)))),
+            _ => false
+        }
+    }
+    // binds the generics of `self` to the parts of `other` they line up with, the first binding wins
+    pub fn infer(&self, other: &Type, generics: &mut HashMap<Symbol, Type>) {
+        match (self, other) {
+            (Self::Generic(name), _) => { generics.entry(*name).or_insert_with(|| other.clone()); }
+            (Self::Table(k1, v1), Self::Table(k2, v2)) => {
+                k1.infer(k2, generics);
+                v1.infer(v2, generics);
+            }
+            (Self::Function { params: p1, varargs: va1, returns: r1 }, Self::Function { params: p2, varargs: va2, returns: r2 }) => {
+                for (t1, t2) in p1.iter().zip(p2).chain(va1.as_deref().zip(va2.as_deref())).chain(r1.iter().zip(r2)) {
+                    t1.infer(t2, generics);
+                }
+            }
+            (Self::Named { name: n1, args: a1 }, Self::Named { name: n2, args: a2 }) if n1 == n2 => {
+                for (t1, t2) in a1.iter().zip(a2) { t1.infer(t2, generics); }
+            }
+            (Self::Union(types), _) => for typ in types { typ.infer(other, generics) }
+            _ => {}
+        }
+    }
+    // the generics a type refers to
+    pub fn generics(&self) -> Vec<Symbol> {
+        match self {
+            Self::Generic(name) => vec![*name],
+            Self::Table(key, value) => [key.generics(), value.generics()].concat(),
+            Self::Function { params, varargs, returns } => params.iter().chain(varargs.as_deref()).chain(returns).flat_map(|x| x.generics()).collect(),
+            Self::Named { name:_, args: types } | Self::Union(types) => types.iter().flat_map(|x| x.generics()).collect(),
+            _ => vec![]
+        }
+    }
+    pub fn substitute(&self, generics: &HashMap<Symbol, Type>) -> Self {
+        match self {
+            Self::Generic(name) => generics.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Self::Table(key, value) => Self::table(key.substitute(generics), value.substitute(generics)),
+            Self::Function { params, varargs, returns } => Self::Function {
+                params: params.iter().map(|x| x.substitute(generics)).collect(),
+                varargs: varargs.as_ref().map(|x| Box::new(x.substitute(generics))),
+                returns: returns.iter().map(|x| x.substitute(generics)).collect(),
+            },
+            Self::Named { name, args } => Self::Named { name: *name, args: args.iter().map(|x| x.substitute(generics)).collect() },
+            Self::Union(types) => Self::union(types.iter().map(|x| x.substitute(generics)).collect()),
+            _ => self.clone()
+        }
+    }
+}
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nil => write!(f, "nil"),
+            Self::Boolean => write!(f, "boolean"),
+            Self::Number => write!(f, "number"),
+            Self::String => write!(f, "string"),
+            Self::Any => write!(f, "any"),
+            Self::Table(key, value) => write!(f, "table<{key}, {value}>"),
+            Self::Function { params, varargs, returns } => write!(f, "function({}{}){}",
+            join(params, ", "),
+            if let Some(varargs) = varargs { format!("{}...{varargs}", if params.is_empty() { "" } else { ", " }) } else { String::new() },
+            match returns.as_slice() {
+                [] => String::new(),
+                [ret] => format!(" -> {ret}"),
+                _ => format!(" -> ({})", join(returns, ", "))
+            }),
+            Self::Named { name, args } => if args.is_empty() {
+                write!(f, "{name}")
+            } else {
+                write!(f, "{name}<{}>", join(args, ", "))
+            }
+            Self::Generic(name) => write!(f, "{name}"),
+            Self::Union(types) => match types.as_slice() {
+                [typ, Self::Nil] if !matches!(typ, Self::Function { params:_, varargs:_, returns:_ }) => write!(f, "{typ}?"),
+                _ => write!(f, "{}", join(types, " | "))
+            }
+            Self::Meta(name) => write!(f, "meta {name}"),
+        }
+    }
+}
\ No newline at end of file
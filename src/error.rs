@@ -1,33 +1,122 @@
-use std::fmt::Display;
-use crate::scanning::{tokens::TokenType, nodes::NodeType};
+use std::{fmt::Display, ops::Range};
+use crate::scanning::{tokens::TokenType, position::{Position, SourceMap}};
+use crate::compiler::types::Type;
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Error {
-    Error(String),
-    InputFile(String),
+pub enum ErrorKind {
+    Msg(String), Usage(String),
+    InputFile, OutputFile,
     IllegalChar(char), ExpectedChar(char), ExpectedHexDigit,
-    
+    MalformedNumber(String), InvalidEscape(String), UnfinishedString,
+    UnfinishedLongString, UnfinishedLongComment,
+
     UnexpectedEOF,
     UnexpectedToken(TokenType), ExpectedToken(TokenType, Option<TokenType>),
 
-    UnexpectedNode(NodeType)
-}
-impl Error {
-    pub fn error(msg: &str) -> Self { Self::Error(msg.to_string()) }
+    UnexpectedNode(String),
+
+    MismatchedType(Type, Type), UndefinedVariable(String),
+    InvalidOperand(TokenType, Type), NotCallable(Type), NotIndexable(Type),
+    ArgumentCount(usize, usize), UnknownType(String), UndefinedField(Type, String),
+    UnknownMetamethod(String), ReturnCount(usize, usize), UnexpectedVarargs,
+    DuplicateLabel(String), UndefinedLabel(String), JumpIntoScope(String), BreakOutsideLoop,
+
+    Unsupported(String, String),
 }
-impl Display for Error {
+impl Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Error(msg) => write!(f, "ERROR: {msg}"),
-            Self::InputFile(path) => write!(f, "ERROR: couldn't find input path {path:?}"),
-            Self::UnexpectedEOF => write!(f, "ERROR: unexpected end of file"),
-            Self::IllegalChar(c) => write!(f, "ERROR: illegal character {c:?}"),
-            Self::ExpectedChar(c) => write!(f, "ERROR: expected character {c:?}"),
-            Self::ExpectedHexDigit => write!(f, "ERROR: expected hexadecimal digit"),
-            Self::UnexpectedToken(token) => write!(f, "ERROR: unexpected {}", token.name()),
-            Self::ExpectedToken(expected, got) => write!(f, "ERROR: expected {}{}", expected.name(),
+            Self::Msg(msg) => write!(f, "{msg}"),
+            Self::Usage(msg) => write!(f, "{msg}"),
+            Self::InputFile => write!(f, "couldn't read input file"),
+            Self::OutputFile => write!(f, "couldn't write to output file"),
+            Self::UnexpectedEOF => write!(f, "unexpected end of file"),
+            Self::IllegalChar(c) => write!(f, "illegal character {c:?}"),
+            Self::ExpectedChar(c) => write!(f, "expected character {c:?}"),
+            Self::ExpectedHexDigit => write!(f, "expected hexadecimal digit"),
+            Self::MalformedNumber(number) => write!(f, "malformed number {number:?}"),
+            Self::InvalidEscape(escape) => write!(f, "invalid escape sequence {escape}"),
+            Self::UnfinishedString => write!(f, "unfinished string"),
+            Self::UnfinishedLongString => write!(f, "unfinished long string"),
+            Self::UnfinishedLongComment => write!(f, "unfinished long comment"),
+            Self::UnexpectedToken(token) => write!(f, "unexpected {}", token.name()),
+            Self::ExpectedToken(expected, got) => write!(f, "expected {}{}", expected.name(),
             if let Some(got) = got { format!(", got {}", got.name()) } else { String::new() }),
-            Self::UnexpectedNode(node) => write!(f, "ERROR: unexpected {}", node.name()),
+            Self::UnexpectedNode(node) => write!(f, "unexpected {node}"),
+            Self::MismatchedType(expected, got) => write!(f, "expected {expected}, got {got}"),
+            Self::UndefinedVariable(id) => write!(f, "undefined variable {id:?}"),
+            Self::InvalidOperand(op, typ) => write!(f, "cannot use {} on {typ}", op.name()),
+            Self::NotCallable(typ) => write!(f, "cannot call {typ}"),
+            Self::NotIndexable(typ) => write!(f, "cannot index {typ}"),
+            Self::ArgumentCount(expected, got) => write!(f, "expected {expected} arguments, got {got}"),
+            Self::UnknownType(typ) => write!(f, "unknown type {typ}"),
+            Self::UndefinedField(typ, field) => write!(f, "{typ} has no field {field:?}"),
+            Self::UnknownMetamethod(method) => write!(f, "{method:?} is neither a metamethod nor a declared method"),
+            Self::ReturnCount(expected, got) => write!(f, "expected {expected} return values, got {got}"),
+            Self::UnexpectedVarargs => write!(f, "cannot use '...' outside a vararg function"),
+            Self::DuplicateLabel(label) => write!(f, "label {label:?} is already defined"),
+            Self::UndefinedLabel(label) => write!(f, "no visible label {label:?} for goto"),
+            Self::JumpIntoScope(label) => write!(f, "goto {label:?} jumps into the scope of a local"),
+            Self::BreakOutsideLoop => write!(f, "break outside a loop"),
+            Self::Unsupported(feature, target) => write!(f, "{target} has no {feature}"),
         }
     }
+}
+
+// what the source map tells about a position: line, column range on that line and the line's text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    ln: usize,
+    col: Range<usize>,
+    line: String
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    kind: Box<ErrorKind>,
+    pos: Option<Position>,
+    path: String,
+    snippet: Option<Box<Snippet>>
+}
+impl Error {
+    pub fn new(kind: ErrorKind, pos: Position) -> Self {
+        Self { kind: Box::new(kind), pos: Some(pos), path: String::new(), snippet: None }
+    }
+    pub fn file(kind: ErrorKind, path: &str) -> Self {
+        Self { kind: Box::new(kind), pos: None, path: path.to_string(), snippet: None }
+    }
+    // errors that don't belong to any file, like bad command line arguments
+    pub fn plain(kind: ErrorKind) -> Self {
+        Self { kind: Box::new(kind), pos: None, path: String::new(), snippet: None }
+    }
+    pub fn kind(&self) -> &ErrorKind { &self.kind }
+    pub fn path(&self) -> &str { &self.path }
+    pub fn pos(&self) -> Option<&Position> { self.pos.as_ref() }
+    // resolves the position to a file, line and column so the error can be displayed
+    pub fn with_source(mut self, map: &SourceMap) -> Self {
+        let Some(pos) = &self.pos else { return self };
+        let Some(file) = map.get(pos.file()) else { return self };
+        let (ln, start) = file.location(pos.span().start);
+        let (end_ln, end) = file.location(pos.span().end);
+        let line = file.line(ln).to_string();
+        // spans over multiple lines are underlined up to the end of the first one
+        let end = if end_ln > ln { line.chars().count() } else { end };
+        self.path = file.path().to_string();
+        self.snippet = Some(Box::new(Snippet { ln, col: start..end, line }));
+        self
+    }
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() { return write!(f, "ERROR: {}", self.kind) }
+        let Some(snippet) = &self.snippet else {
+            return write!(f, "ERROR: {}\n  --> {}", self.kind, self.path)
+        };
+        let Snippet { ln, col, line } = snippet.as_ref();
+        write!(f, "ERROR: {}\n  --> {}:{}:{}", self.kind, self.path, ln + 1, col.start + 1)?;
+        let ln = (ln + 1).to_string();
+        let margin = " ".repeat(ln.len());
+        let indent = line.chars().take(col.start).map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>();
+        write!(f, "\n{margin} |\n{ln} | {line}\n{margin} | {indent}{}", "^".repeat(col.len().max(1)))
+    }
 }
\ No newline at end of file
@@ -2,35 +2,106 @@
 mod error;
 mod scanning;
 mod compiler;
-use std::{env, fs};
-use error::Error;
-use scanning::{tokens, lexer, nodes, parser};
-use compiler::{types, check};
+mod cli;
+use std::{env, fs, io::{self, Read, Write}, path::Path, process::ExitCode};
+use error::{Error, ErrorKind};
+use scanning::{tokens, lexer, nodes, parser, emit::{self, Emit}, position::SourceMap};
+use compiler::{types, check, codegen};
+use cli::{Args, Command};
 
-fn run() -> Result<(), Error> {
-    let args: Vec<String> = env::args().collect();
-    let mut args = args.iter();
-    args.next();
-    let Some(input_path) = args.next() else {
-        println!("USAGE: luo [input path] ([output path])");
-        return Ok(())
+fn read(args: &Args) -> Result<String, Error> {
+    let mut text = String::new();
+    let ok = if args.is_stdin() {
+        io::stdin().read_to_string(&mut text).is_ok()
+    } else {
+        fs::read_to_string(args.path()).map(|x| text = x).is_ok()
     };
-    let Ok(text) = fs::read_to_string(input_path) else {
-        return Err(Error::InputFile(input_path.clone()))
+    if !ok { return Err(Error::file(ErrorKind::InputFile, args.path())) }
+    Ok(text)
+}
+fn print(text: &str) -> Result<(), Error> {
+    if io::stdout().write_all(text.as_bytes()).is_err() {
+        return Err(Error::file(ErrorKind::OutputFile, "<stdout>"))
+    }
+    Ok(())
+}
+fn write(args: &Args, code: &str) -> Result<Option<String>, Error> {
+    let output_path = match args.out() {
+        Some(path) => path.to_string(),
+        // stdin has no path to derive the output from
+        None if args.is_stdin() => "-".to_string(),
+        None => {
+            let mut path = Path::new(args.path()).with_extension("lua").to_string_lossy().to_string();
+            if path == args.path() { path.push_str(".lua"); }
+            path
+        }
     };
-    let output_path = args.next();
+    if output_path == "-" {
+        print(code)?;
+        return Ok(None)
+    }
+    if fs::write(&output_path, code).is_err() {
+        return Err(Error::file(ErrorKind::OutputFile, &output_path))
+    }
+    Ok(Some(output_path))
+}
+
+fn run(args: &Args) -> Result<(), Vec<Error>> {
+    let path = args.path();
+    let mut map = SourceMap::default();
+    let file = map.add(path, read(args).map_err(|e| vec![e])?);
+    let text = map.get(file).unwrap().text();
+    let source = |e: Error| vec![e.with_source(&map)];
     // lexing
-    let tokens = lexer::lex(input_path, text)?;
-    println!("{tokens:?}");
-    // parsing
-    let ast = parser::parse(input_path, tokens)?;
-    println!("{}", ast.format(0, false));
+    if args.command() == Command::Lex {
+        let tokens = lexer::lex(file, text).map_err(source)?;
+        let dump = match args.emit() {
+            Emit::Text => tokens.iter().map(|x| format!("{} {}\n", map.describe(x.pos()), x.token().display())).collect::<String>(),
+            format => format!("{}\n", emit::tokens(&tokens).render(format))
+        };
+        return print(&dump).map_err(source)
+    }
+    // parsing, the tokens are lexed along the way and the partial tree is still dumped when there are errors
+    let (ast, errors) = parser::parse(file, lexer::Lexer::new(file, text));
+    if args.command() == Command::Parse {
+        let dump = match args.emit() {
+            Emit::Text => ast.format(ast.root(), 0, false),
+            format => emit::node(&ast, ast.root()).render(format)
+        };
+        print(&format!("{dump}\n")).map_err(source)?
+    }
+    if !errors.is_empty() { return Err(errors.into_iter().map(|e| e.with_source(&map)).collect()) }
+    if args.command() == Command::Parse { return Ok(()) }
     // type checking
+    check::check(&ast).map_err(source)?;
+    if args.command() == Command::Check {
+        if !args.quiet() { eprintln!("{path}: ok") }
+        return Ok(())
+    }
     // compilation
+    let code = codegen::generate(&ast, args.target()).map_err(source)?;
+    let output_path = write(args, &code).map_err(source)?;
+    if let (Some(output_path), false) = (output_path, args.quiet()) { eprintln!("{path} -> {output_path}") }
     Ok(())
 }
 
-fn main() {
-    let res = run();
-    if let Some(err) = res.err() { eprint!("{err}") }
-}
+fn main() -> ExitCode {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            return ExitCode::from(2)
+        }
+    };
+    if args.help() {
+        println!("{}", cli::USAGE);
+        return ExitCode::SUCCESS
+    }
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(errors) => {
+            eprintln!("{}", errors.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("\n\n"));
+            ExitCode::FAILURE
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,196 @@
+use std::ops::Range;
+use super::{nodes::{Ast, NodeId, NodeType, TableField}, tokens::{Token, TokenType}};
+
+// dumps of tokens and syntax trees for other programs, the format is described in the readme
+// both formats are rendered from the same values, so they always hold the same information
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit { Text, Json, Sexp }
+impl Emit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            "sexp" => Some(Self::Sexp),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null, Boolean(bool), Integer(i64), Float(f64), String(String), List(Vec<Value>),
+    // a token or node with its byte span and named fields
+    Item { kind: &'static str, span: Range<usize>, fields: Vec<(&'static str, Value)> }
+}
+impl Value {
+    pub fn string(v: impl ToString) -> Self { Self::String(v.to_string()) }
+    pub fn render(&self, emit: Emit) -> String {
+        let mut out = String::new();
+        match emit {
+            Emit::Sexp => self.sexp(&mut out),
+            _ => self.json(&mut out)
+        }
+        out
+    }
+    pub fn json(&self, out: &mut String) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Boolean(v) => out.push_str(&v.to_string()),
+            Self::Integer(v) => out.push_str(&v.to_string()),
+            Self::Float(v) => out.push_str(&float(*v)),
+            Self::String(v) => out.push_str(&string(v)),
+            Self::List(values) => {
+                out.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 { out.push_str(", ") }
+                    value.json(out);
+                }
+                out.push(']');
+            }
+            Self::Item { kind, span, fields } => {
+                out.push_str(&format!("{{\"kind\": {}, \"span\": [{}, {}]", string(kind), span.start, span.end));
+                for (name, value) in fields {
+                    out.push_str(&format!(", {}: ", string(name)));
+                    value.json(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+    pub fn sexp(&self, out: &mut String) {
+        match self {
+            Self::Null => out.push_str("nil"),
+            Self::List(values) => {
+                out.push('(');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 { out.push(' ') }
+                    value.sexp(out);
+                }
+                out.push(')');
+            }
+            Self::Item { kind, span, fields } => {
+                out.push_str(&format!("({kind} {} {}", span.start, span.end));
+                for (name, value) in fields {
+                    out.push_str(&format!(" :{name} "));
+                    value.sexp(out);
+                }
+                out.push(')');
+            }
+            _ => self.json(out)
+        }
+    }
+}
+
+// floats always have a fraction or an exponent, the ones json can't hold become strings
+pub fn float(v: f64) -> String {
+    if v.is_nan() { return "\"nan\"".to_string() }
+    if v.is_infinite() { return if v > 0.0 { "\"inf\"" } else { "\"-inf\"" }.to_string() }
+    format!("{v:?}")
+}
+pub fn string(v: &str) -> String {
+    let mut string = String::from('"');
+    for c in v.chars() {
+        match c {
+            '"' => string.push_str("\\\""),
+            '\\' => string.push_str("\\\\"),
+            '\n' => string.push_str("\\n"),
+            '\r' => string.push_str("\\r"),
+            '\t' => string.push_str("\\t"),
+            _ if c.is_control() => string.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => string.push(c)
+        }
+    }
+    string.push('"');
+    string
+}
+
+pub fn token(token: &Token) -> Value {
+    let (kind, value) = match token.token() {
+        TokenType::ID(v) => ("name", Value::string(v)),
+        TokenType::Integer(v) => ("integer", Value::Integer(*v)),
+        TokenType::Float(v) => ("float", Value::Float(*v)),
+        TokenType::String(v) => ("string", Value::string(v)),
+        TokenType::Boolean(v) => ("boolean", Value::Boolean(*v)),
+        TokenType::Nil => ("nil", Value::Null),
+        typ => {
+            let text = typ.display();
+            (if text.chars().all(|c| c.is_ascii_alphabetic()) { "keyword" } else { "symbol" }, Value::String(text))
+        }
+    };
+    let fields = if value == Value::Null { vec![] } else { vec![("value", value)] };
+    Value::Item { kind, span: token.pos().span().clone(), fields }
+}
+pub fn tokens(tokens: &[Token]) -> Value {
+    Value::List(tokens.iter().map(token).collect())
+}
+
+pub fn node(ast: &Ast, id: NodeId) -> Value {
+    let node = |id: &NodeId| node(ast, *id);
+    let nodes = |ids: &[NodeId]| Value::List(ids.iter().map(node).collect());
+    let optional = |id: &Option<NodeId>| id.as_ref().map_or(Value::Null, node);
+    let names = |names: &[_]| Value::List(names.iter().map(Value::string).collect());
+    let (kind, fields) = match ast.get(id).node() {
+        NodeType::Chunk(body) => ("Chunk", vec![("body", nodes(body))]),
+        NodeType::DoBlock(body) => ("DoBlock", vec![("body", nodes(body))]),
+        NodeType::Body(body) => ("Body", vec![("body", nodes(body))]),
+        NodeType::ID(name) => ("ID", vec![("name", Value::string(name))]),
+        NodeType::Integer(v) => ("Integer", vec![("value", Value::Integer(*v))]),
+        NodeType::Float(v) => ("Float", vec![("value", Value::Float(*v))]),
+        NodeType::Boolean(v) => ("Boolean", vec![("value", Value::Boolean(*v))]),
+        NodeType::String(v) => ("String", vec![("value", Value::string(v))]),
+        NodeType::Nil => ("Nil", vec![]),
+        NodeType::Varargs => ("Varargs", vec![]),
+        NodeType::Expr(expr) => ("Expr", vec![("expr", node(expr))]),
+        NodeType::Binary { left, op, right } => ("Binary", vec![("left", node(left)), ("op", Value::String(op.display())), ("right", node(right))]),
+        NodeType::Unary { op, node: operand } => ("Unary", vec![("op", Value::String(op.display())), ("node", node(operand))]),
+        NodeType::Field { left, right, expr } => ("Field", vec![("left", node(left)), ("right", node(right)), ("expr", Value::Boolean(*expr))]),
+        NodeType::Call { head, args } => ("Call", vec![("head", node(head)), ("args", nodes(args))]),
+        NodeType::SelfCall { head, field, args } => ("SelfCall", vec![("head", node(head)), ("field", Value::string(field)), ("args", nodes(args))]),
+        NodeType::Assign(var, expr) => ("Assign", vec![("var", node(var)), ("expr", node(expr))]),
+        NodeType::AssignVars(vars, exprs) => ("AssignVars", vec![("vars", nodes(vars)), ("exprs", nodes(exprs))]),
+        NodeType::LocalAssign(var, expr) => ("LocalAssign", vec![("var", node(var)), ("expr", optional(expr))]),
+        NodeType::LocalAssignVars(vars, exprs) => ("LocalAssignVars", vec![("vars", nodes(vars)), ("exprs", nodes(exprs))]),
+        NodeType::Return(exprs) => ("Return", vec![("exprs", nodes(exprs))]),
+        NodeType::Break => ("Break", vec![]),
+        NodeType::Goto(name) => ("Goto", vec![("name", Value::string(name))]),
+        NodeType::Label(name) => ("Label", vec![("name", Value::string(name))]),
+        NodeType::If { conds, cases, else_case } => ("If", vec![("conds", nodes(conds)), ("cases", nodes(cases)), ("else_case", optional(else_case))]),
+        NodeType::While { cond, body } => ("While", vec![("cond", node(cond)), ("body", node(body))]),
+        NodeType::Repeat { body, cond } => ("Repeat", vec![("body", node(body)), ("cond", node(cond))]),
+        NodeType::ForIn { vars, iter, body } => ("ForIn", vec![("vars", names(vars)), ("iter", node(iter)), ("body", node(body))]),
+        NodeType::For { var, start, end, step, body } => ("For", vec![("var", Value::string(var)), ("start", node(start)), ("end", node(end)),
+            ("step", optional(step)), ("body", node(body))]),
+        NodeType::Function { path, method, params, varargs, ret, body } => ("Function", vec![("path", names(path)),
+            ("method", method.map_or(Value::Null, Value::string)), ("params", nodes(params)), ("varargs", Value::Boolean(*varargs)),
+            ("ret", optional(ret)), ("body", node(body))]),
+        NodeType::LocalFunction { name, params, varargs, ret, body } => ("LocalFunction", vec![("name", Value::string(name)),
+            ("params", nodes(params)), ("varargs", Value::Boolean(*varargs)), ("ret", optional(ret)), ("body", node(body))]),
+        NodeType::Lambda { params, varargs, ret, body } => ("Lambda", vec![("params", nodes(params)), ("varargs", Value::Boolean(*varargs)),
+            ("ret", optional(ret)), ("body", node(body))]),
+        NodeType::Meta { name, generics, funcs } => ("Meta", vec![("name", Value::string(name)), ("generics", names(generics)), ("funcs", nodes(funcs))]),
+        NodeType::Implement { name, generics, args, funcs } => ("Implement", vec![("name", Value::string(name)), ("generics", names(generics)),
+            ("args", nodes(args)), ("funcs", nodes(funcs))]),
+        NodeType::Table(fields) => ("Table", vec![("fields", Value::List(fields.iter().map(|x| table_field(ast, x)).collect()))]),
+        NodeType::Var { name, typ } => ("Var", vec![("name", Value::string(name)), ("typ", optional(typ))]),
+        NodeType::TypeName { name, args } => ("TypeName", vec![("name", Value::string(name)), ("args", nodes(args))]),
+        NodeType::TypeFn { params, varargs, ret } => ("TypeFn", vec![("params", nodes(params)), ("varargs", optional(varargs)), ("ret", optional(ret))]),
+        NodeType::TypeOptional(typ) => ("TypeOptional", vec![("typ", node(typ))]),
+        NodeType::TypeUnion(types) => ("TypeUnion", vec![("types", nodes(types))]),
+        NodeType::TypeTuple(types) => ("TypeTuple", vec![("types", nodes(types))]),
+        NodeType::Error => ("Error", vec![]),
+    };
+    Value::Item { kind, span: ast.pos(id).span().clone(), fields }
+}
+// table fields have no position of their own, they get the span of their value and key
+pub fn table_field(ast: &Ast, field: &TableField) -> Value {
+    let (kind, value, mut fields) = match field {
+        TableField::Item(value) => ("Item", value, vec![]),
+        TableField::Named(name, value) => ("Named", value, vec![("name", Value::string(name))]),
+        TableField::Keyed(key, value) => ("Keyed", value, vec![("key", node(ast, *key))]),
+    };
+    let mut span = ast.pos(*value).span().clone();
+    if let TableField::Keyed(key, _) = field { span.start = ast.pos(*key).span().start }
+    fields.push(("value", node(ast, *value)));
+    Value::Item { kind, span, fields }
+}
\ No newline at end of file
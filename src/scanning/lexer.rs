@@ -1,34 +1,74 @@
-use crate::error::Error;
-use super::{tokens::{TokenType, Token}, position::Position};
-use std::i64;
+use crate::error::{Error, ErrorKind};
+use super::{tokens::{TokenType, Token, Trivia, TriviaKind}, position::{Position, FileId}, symbol::Symbol};
 
-pub struct Lexer {
-    path: String,
-    text: String,
+pub struct Lexer<'a> {
+    file: FileId,
+    text: &'a str,
     idx: usize,
-    ln: usize,
-    col: usize,
 }
-impl Lexer {
-    pub fn new(path: &String, text: String) -> Self {
-        Self { path: path.clone(), text, idx: 0, ln: 0, col: 0 }
+impl<'a> Lexer<'a> {
+    pub fn new(file: FileId, text: &'a str) -> Self {
+        let mut lexer = Self { file, text, idx: 0 };
+        if lexer.text.starts_with('\u{feff}') { lexer.idx = '\u{feff}'.len_utf8(); }
+        // like in lua any first line starting with '#' is skipped, the line break after it is kept
+        if lexer.text[lexer.idx..].starts_with('#') {
+            while lexer.get().is_some_and(|c| c != '\n') { lexer.advance(); }
+        }
+        lexer
     }
     pub fn get(&self) -> Option<char> {
-        self.text.get(self.idx..self.idx+1)?.chars().next()
+        self.text.get(self.idx..)?.chars().next()
     }
     pub fn advance(&mut self) {
-        self.idx += 1;
-        self.col += 1;
-        if self.get() == Some('\n') {
-            self.ln += 1;
-            self.col = 0;
-        }
+        if let Some(c) = self.get() { self.idx += c.len_utf8(); }
     }
     pub fn pos(&self) -> Position {
-        Position::new(self.ln..self.ln+1, self.col..self.col+1)
+        Position::new(self.file, self.idx..self.idx + self.get().map_or(0, char::len_utf8))
+    }
+    pub fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.pos())
+    }
+    pub fn error_at(&self, kind: ErrorKind, pos: Position) -> Error {
+        Error::new(kind, pos)
+    }
+    // moves the current char into `text`
+    pub fn take(&mut self, text: &mut String, pos: &mut Position) {
+        let Some(c) = self.get() else { return };
+        text.push(c);
+        pos.extend(&self.pos());
+        self.advance();
+    }
+    // skips the whitespace or comment at the cursor
+    pub fn trivia(&mut self) -> Result<Option<TriviaKind>, Error> {
+        match self.get() {
+            Some('\n' | '\r') => {
+                self.newline();
+                Ok(Some(TriviaKind::Newline))
+            }
+            Some(' ' | '\t' | '\x0b' | '\x0c') => {
+                while let Some(' ' | '\t' | '\x0b' | '\x0c') = self.get() { self.advance(); }
+                Ok(Some(TriviaKind::Whitespace))
+            }
+            Some('-') if self.text[self.idx..].starts_with("--") => {
+                let mut pos = self.pos();
+                self.advance();
+                self.advance();
+                if let Some(level) = self.long_bracket_level() {
+                    self.long_bracket(level, &mut pos, ErrorKind::UnfinishedLongComment)?;
+                    return Ok(Some(TriviaKind::BlockComment))
+                }
+                let doc = self.get() == Some('-');
+                while let Some(c) = self.get() {
+                    if c == '\n' || c == '\r' { break }
+                    self.advance();
+                }
+                Ok(Some(if doc { TriviaKind::DocComment } else { TriviaKind::LineComment }))
+            }
+            _ => Ok(None)
+        }
     }
     pub fn token(&mut self) -> Result<Option<Token>, Error> {
-        while let Some(' ' | '\t' | '\n' | '\r') = self.get() { self.advance(); }
+        while self.trivia()?.is_some() {}
         if let Some(c) = self.get() {
             let mut pos = self.pos();
             match c {
@@ -38,12 +78,10 @@ impl Lexer {
                 }
                 '-' => {
                     self.advance();
-                    if self.get() == Some('-') {
+                    if self.get() == Some('>') {
+                        pos.extend(&self.pos());
                         self.advance();
-                        while let Some(c) = self.get() {
-                            if c == '\n' { self.advance(); break }
-                            self.advance();
-                        }
+                        return Ok(Some(Token::new(TokenType::Arrow, pos)))
                     }
                     Ok(Some(Token::new(TokenType::Sub, pos)))
                 }
@@ -53,6 +91,11 @@ impl Lexer {
                 }
                 '/' => {
                     self.advance();
+                    if self.get() == Some('/') {
+                        pos.extend(&self.pos());
+                        self.advance();
+                        return Ok(Some(Token::new(TokenType::IDiv, pos)))
+                    }
                     Ok(Some(Token::new(TokenType::Div, pos)))
                 }
                 '%' => {
@@ -83,7 +126,7 @@ impl Lexer {
                         self.advance();
                         return Ok(Some(Token::new(TokenType::NE, pos)))
                     }
-                    Err(Error::ExpectedChar('='))
+                    Ok(Some(Token::new(TokenType::BXor, pos)))
                 }
                 '<' => {
                     self.advance();
@@ -92,6 +135,11 @@ impl Lexer {
                         self.advance();
                         return Ok(Some(Token::new(TokenType::LE, pos)))
                     }
+                    if self.get() == Some('<') {
+                        pos.extend(&self.pos());
+                        self.advance();
+                        return Ok(Some(Token::new(TokenType::Shl, pos)))
+                    }
                     Ok(Some(Token::new(TokenType::LT, pos)))
                 }
                 '>' => {
@@ -101,6 +149,11 @@ impl Lexer {
                         self.advance();
                         return Ok(Some(Token::new(TokenType::GE, pos)))
                     }
+                    if self.get() == Some('>') {
+                        pos.extend(&self.pos());
+                        self.advance();
+                        return Ok(Some(Token::new(TokenType::Shr, pos)))
+                    }
                     Ok(Some(Token::new(TokenType::GT, pos)))
                 }
                 '(' => {
@@ -120,6 +173,10 @@ impl Lexer {
                     Ok(Some(Token::new(TokenType::TableOut, pos)))
                 }
                 '[' => {
+                    if let Some(level) = self.long_bracket_level() {
+                        let string = self.long_bracket(level, &mut pos, ErrorKind::UnfinishedLongString)?;
+                        return Ok(Some(Token::new(TokenType::String(Symbol::intern(&string)), pos)))
+                    }
                     self.advance();
                     Ok(Some(Token::new(TokenType::IndexIn, pos)))
                 }
@@ -129,12 +186,33 @@ impl Lexer {
                 }
                 ':' => {
                     self.advance();
+                    if self.get() == Some(':') {
+                        pos.extend(&self.pos());
+                        self.advance();
+                        return Ok(Some(Token::new(TokenType::Label, pos)))
+                    }
                     Ok(Some(Token::new(TokenType::Rep, pos)))
                 }
                 ',' => {
                     self.advance();
                     Ok(Some(Token::new(TokenType::Sep, pos)))
                 }
+                ';' => {
+                    self.advance();
+                    Ok(Some(Token::new(TokenType::Semi, pos)))
+                }
+                '?' => {
+                    self.advance();
+                    Ok(Some(Token::new(TokenType::Opt, pos)))
+                }
+                '&' => {
+                    self.advance();
+                    Ok(Some(Token::new(TokenType::BAnd, pos)))
+                }
+                '|' => {
+                    self.advance();
+                    Ok(Some(Token::new(TokenType::BOr, pos)))
+                }
                 '.' => {
                     self.advance();
                     if self.get() == Some('.') {
@@ -147,100 +225,355 @@ impl Lexer {
                         }
                         return Ok(Some(Token::new(TokenType::Concat, pos)))
                     }
+                    if let Some('0'..='9') = self.get() {
+                        return Ok(Some(self.number(".".to_string(), pos)?))
+                    }
                     Ok(Some(Token::new(TokenType::Field, pos)))
                 }
                 '"' | '\'' => {
                     let stop = c;
                     self.advance();
-                    let mut string = String::new();
+                    let mut bytes = vec![];
                     while let Some(c) = self.get() {
                         if c == stop { break }
-                        if c == '\\' {
-                            pos.extend(&self.pos());
-                            self.advance();
-                            match self.get() {
-                                Some('n') => { string.push('\n'); }
-                                Some('t') => { string.push('\t'); }
-                                Some('r') => { string.push('\r'); }
-                                Some('0') => { string.push('\0'); }
-                                Some(c) => { string.push(c); }
-                                None => return Err(Error::UnexpectedEOF)
+                        match c {
+                            '\n' | '\r' => return Err(self.error(ErrorKind::UnfinishedString)),
+                            '\\' => self.escape(&mut bytes)?,
+                            _ => {
+                                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                                self.advance();
                             }
-                            pos.extend(&self.pos());
-                            self.advance();
-                        } else {
-                            string.push(c);
-                            pos.extend(&self.pos());
-                            self.advance();
                         }
                     }
-                    if self.get() == None { return Err(Error::UnexpectedEOF) }
-                    self.advance();
-                    Ok(Some(Token::new(TokenType::String(string), pos)))
-                }
-                _ if c.is_digit(10) => {
-                    let mut number = String::from(c);
+                    if self.get().is_none() { return Err(self.error(ErrorKind::UnexpectedEOF)) }
+                    pos.extend(&self.pos());
                     self.advance();
-                    while let Some(c) = self.get() {
-                        if !c.is_digit(10) { break }
-                        number.push(c);
-                        pos.extend(&self.pos());
-                        self.advance();
-                    }
-                    if self.get() == Some('x') && number == "0" {
-                        self.advance();
-                        number.clear();
-                        pos = self.pos();
-                        if let Some(c) = self.get() {
-                            if !c.is_digit(16) {
-                                return Err(Error::ExpectedHexDigit)
-                            }
-                        }
-                        while let Some(c) = self.get() {
-                            if !c.is_digit(16) { break }
-                            number.push(c);
-                            pos.extend(&self.pos());
-                            self.advance();
-                        }
-                        return Ok(Some(Token::new(TokenType::Number(i64::from_str_radix(number.as_str(), 16).unwrap() as f64), pos)))
-                    } else if self.get() == Some('.') {
-                        number.push(c);
-                        pos.extend(&self.pos());
-                        self.advance();
-                        while let Some(c) = self.get() {
-                            if !c.is_digit(10) { break }
-                            number.push(c);
-                            pos.extend(&self.pos());
-                            self.advance();
-                        }
-                        return Ok(Some(Token::new(TokenType::Number(number.parse().unwrap()), pos)))
-                    }
-                    Ok(Some(Token::new(TokenType::Number(number.parse().unwrap()), pos)))
+                    Ok(Some(Token::new(TokenType::String(Symbol::intern_bytes(&bytes)), pos)))
                 }
-                _ if c.is_alphabetic() || c == '_' => {
+                _ if c.is_ascii_digit() => Ok(Some(self.number(String::new(), pos)?)),
+                _ if c.is_ascii_alphabetic() || c == '_' => {
                     let mut id = String::from(c);
                     self.advance();
                     while let Some(c) = self.get() {
-                        if !c.is_alphabetic() && c != '_' { break }
+                        if !c.is_ascii_alphanumeric() && c != '_' { break }
                         id.push(c);
                         pos.extend(&self.pos());
                         self.advance();
                     }
-                    Ok(Some(Token::new(TokenType::from_name(id), pos)))
+                    Ok(Some(Token::new(TokenType::from_name(&id), pos)))
                 }
-                _ => Err(Error::IllegalChar(c))
+                _ => Err(self.error(ErrorKind::IllegalChar(c)))
             }
         } else { Ok(None) }
     }
-    pub fn lex(&mut self) -> Result<Vec<Token>, Error> {
+    // skips a line break, `\r\n` and `\n\r` count as one
+    pub fn newline(&mut self) -> bool {
+        let Some(c @ ('\n' | '\r')) = self.get() else { return false };
+        self.advance();
+        if let Some(next @ ('\n' | '\r')) = self.get() {
+            if next != c { self.advance(); }
+        }
+        true
+    }
+    // the level of the long bracket opening at the cursor, `[==[` has level 2
+    pub fn long_bracket_level(&self) -> Option<usize> {
+        let rest = self.text.get(self.idx..)?.strip_prefix('[')?;
+        let level = rest.len() - rest.trim_start_matches('=').len();
+        rest[level..].starts_with('[').then_some(level)
+    }
+    // reads everything up to the matching closing bracket, line breaks are normalized to `\n`
+    pub fn long_bracket(&mut self, level: usize, pos: &mut Position, unfinished: ErrorKind) -> Result<String, Error> {
+        let start = pos.clone();
+        for _ in 0..level + 2 { self.advance(); }
+        // a line break directly after the opening bracket isn't part of the string
+        self.newline();
+        let close = format!("]{}]", "=".repeat(level));
+        let mut string = String::new();
+        while !self.text.get(self.idx..).is_some_and(|x| x.starts_with(&close)) {
+            let Some(c) = self.get() else { return Err(self.error_at(unfinished, start)) };
+            if self.newline() {
+                string.push('\n');
+            } else {
+                string.push(c);
+                self.advance();
+            }
+        }
+        for _ in 0..close.len() {
+            pos.extend(&self.pos());
+            self.advance();
+        }
+        Ok(string)
+    }
+    // reads a numeral the way the reference lexer does and converts it afterwards
+    pub fn number(&mut self, mut number: String, mut pos: Position) -> Result<Token, Error> {
+        let mut exponent = ['e', 'E'];
+        if number.is_empty() && self.get() == Some('0') {
+            self.take(&mut number, &mut pos);
+            if let Some('x' | 'X') = self.get() {
+                self.take(&mut number, &mut pos);
+                exponent = ['p', 'P'];
+            }
+        }
+        while let Some(c) = self.get() {
+            if exponent.contains(&c) {
+                self.take(&mut number, &mut pos);
+                if let Some('+' | '-') = self.get() { self.take(&mut number, &mut pos); }
+            } else if c.is_ascii_hexdigit() || c == '.' {
+                self.take(&mut number, &mut pos);
+            } else { break }
+        }
+        // a numeral running into a name is malformed as a whole
+        while let Some(c) = self.get() {
+            if !c.is_ascii_alphanumeric() && c != '_' { break }
+            self.take(&mut number, &mut pos);
+        }
+        let Some(token) = parse_number(&number) else {
+            return Err(self.error_at(ErrorKind::MalformedNumber(number), pos))
+        };
+        Ok(Token::new(token, pos))
+    }
+    // decodes the escape sequence at the cursor, strings are bytes and don't have to be valid utf-8
+    pub fn escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let (start, mut pos) = (self.idx, self.pos());
+        self.advance();
+        let Some(c) = self.get() else { return Err(self.error(ErrorKind::UnexpectedEOF)) };
+        let byte = match c {
+            'a' => Some(7), 'b' => Some(8), 'f' => Some(12), 'n' => Some(b'\n'), 'r' => Some(b'\r'),
+            't' => Some(b'\t'), 'v' => Some(11), '\\' => Some(b'\\'), '"' => Some(b'"'), '\'' => Some(b'\''),
+            _ => None
+        };
+        if let Some(byte) = byte {
+            bytes.push(byte);
+            self.advance();
+            return Ok(())
+        }
+        match c {
+            // an escaped line break is kept
+            '\n' | '\r' => {
+                bytes.push(b'\n');
+                self.newline();
+            }
+            'z' => {
+                self.advance();
+                while let Some(' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c') = self.get() { self.advance(); }
+            }
+            'x' => {
+                self.advance();
+                let mut v = 0;
+                for _ in 0..2 {
+                    let Some(digit) = self.get().and_then(|c| c.to_digit(16)) else {
+                        return Err(self.error(ErrorKind::ExpectedHexDigit))
+                    };
+                    v = v * 16 + digit;
+                    self.advance();
+                }
+                bytes.push(v as u8);
+            }
+            'u' => {
+                self.advance();
+                if self.get() != Some('{') { return Err(self.error(ErrorKind::ExpectedChar('{'))) }
+                self.advance();
+                let mut v: Option<u32> = None;
+                while let Some(digit) = self.get().and_then(|c| c.to_digit(16)) {
+                    v = Some(v.unwrap_or(0).saturating_mul(16).saturating_add(digit));
+                    self.advance();
+                }
+                let Some(v) = v else { return Err(self.error(ErrorKind::ExpectedHexDigit)) };
+                if self.get() != Some('}') { return Err(self.error(ErrorKind::ExpectedChar('}'))) }
+                pos.extend(&self.pos());
+                self.advance();
+                if v > 0x7fff_ffff {
+                    return Err(self.error_at(ErrorKind::InvalidEscape(self.text[start..self.idx].to_string()), pos))
+                }
+                utf8_escape(v, bytes);
+            }
+            '0'..='9' => {
+                let mut v = 0;
+                for _ in 0..3 {
+                    let Some(digit) = self.get().and_then(|c| c.to_digit(10)) else { break };
+                    v = v * 10 + digit;
+                    pos.extend(&self.pos());
+                    self.advance();
+                }
+                if v > 255 {
+                    return Err(self.error_at(ErrorKind::InvalidEscape(self.text[start..self.idx].to_string()), pos))
+                }
+                bytes.push(v as u8);
+            }
+            _ => {
+                pos.extend(&self.pos());
+                return Err(self.error_at(ErrorKind::InvalidEscape(format!("\\{c}")), pos))
+            }
+        }
+        Ok(())
+    }
+    pub fn trivia_text(&mut self) -> Result<Option<Trivia>, Error> {
+        let start = self.idx;
+        Ok(self.trivia()?.map(|kind| Trivia::new(kind, &self.text[start..self.idx])))
+    }
+    // keeps the whitespace and comments around every token, returns the trivia after the last one separately
+    pub fn lex_lossless(&mut self) -> Result<(Vec<Token>, Vec<Trivia>), Error> {
         let mut tokens = vec![];
-        while self.get().is_some() {
-            if let Some(token) = self.token()? { tokens.push(token); }
+        // the byte order mark and shebang line were already skipped
+        let prefix = &self.text[..self.idx];
+        let shebang = prefix.strip_prefix('\u{feff}');
+        let mut leading = vec![];
+        if shebang.is_some() { leading.push(Trivia::new(TriviaKind::Bom, "\u{feff}")) }
+        let shebang = shebang.unwrap_or(prefix);
+        if !shebang.is_empty() { leading.push(Trivia::new(TriviaKind::Shebang, shebang)) }
+        loop {
+            while let Some(trivia) = self.trivia_text()? { leading.push(trivia); }
+            let start = self.idx;
+            let Some(token) = self.token()? else { break };
+            let text = self.text[start..self.idx].to_string();
+            // everything up to the end of the line belongs to the token before it
+            let mut trailing = vec![];
+            while !matches!(self.get(), Some('\n' | '\r') | None) {
+                let Some(trivia) = self.trivia_text()? else { break };
+                trailing.push(trivia);
+            }
+            tokens.push(token.with_trivia(text, std::mem::take(&mut leading), trailing));
+        }
+        Ok((tokens, leading))
+    }
+    pub fn lex(&mut self) -> Result<Vec<Token>, Error> {
+        self.collect()
+    }
+}
+// tokens are read one at a time, the stream ends after the first error
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.token().transpose();
+        if let Some(Err(_)) = token { self.idx = self.text.len() }
+        token
+    }
+}
+
+pub fn lex(file: FileId, text: &str) -> Result<Vec<Token>, Error> {
+    Lexer::new(file, text).lex()
+}
+pub fn lex_lossless(file: FileId, text: &str) -> Result<(Vec<Token>, Vec<Trivia>), Error> {
+    Lexer::new(file, text).lex_lossless()
+}
+
+// encodes a `\u{...}` escape like the reference implementation, which also allows surrogates and values up to 2^31
+pub fn utf8_escape(mut v: u32, bytes: &mut Vec<u8>) {
+    if v < 0x80 { return bytes.push(v as u8) }
+    let mut continuation = vec![];
+    // the most the first byte can hold, it has one bit less for every continuation byte
+    let mut first = 0x3f;
+    while continuation.is_empty() || v > first {
+        continuation.push(0x80 | (v & 0x3f) as u8);
+        v >>= 6;
+        first >>= 1;
+    }
+    bytes.push(((!first << 1) | v) as u8);
+    bytes.extend(continuation.iter().rev());
+}
+
+// converts a numeral read by `Lexer::number` to an integer or float token, `None` if it is malformed
+pub fn parse_number(text: &str) -> Option<TokenType> {
+    let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) else {
+        // decimal integers that don't fit become floats like in lua
+        if text.chars().all(|c| c.is_ascii_digit()) {
+            if let Ok(v) = text.parse() { return Some(TokenType::Integer(v)) }
         }
-        Ok(tokens)
+        // rust would also accept "inf" and "nan", but numerals always start with a digit or a dot
+        return text.parse().ok().map(TokenType::Float)
+    };
+    let (mantissa, exponent) = match hex.find(['p', 'P']) {
+        Some(i) => (&hex[..i], Some(binary_exponent(&hex[i+1..])?)),
+        None => (hex, None)
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int.is_empty() && frac.is_empty() { return None }
+    if !int.chars().chain(frac.chars()).all(|c| c.is_ascii_hexdigit()) { return None }
+    if exponent.is_none() && !mantissa.contains('.') {
+        // hexadecimal integers wrap around like in lua
+        let v = int.chars().fold(0u64, |v, c| v.wrapping_mul(16).wrapping_add(c.to_digit(16).unwrap() as u64));
+        return Some(TokenType::Integer(v as i64))
     }
+    let v = int.chars().chain(frac.chars()).fold(0.0, |v, c| v * 16.0 + c.to_digit(16).unwrap() as f64);
+    // zero stays zero however large the exponent is
+    if v == 0.0 { return Some(TokenType::Float(0.0)) }
+    let exponent = exponent.unwrap_or(0).saturating_sub(4 * frac.len() as i32);
+    // applied in two steps so tiny and huge results don't over- or underflow halfway
+    Some(TokenType::Float(v * 2f64.powi(exponent / 2) * 2f64.powi(exponent - exponent / 2)))
+}
+// exponents that don't fit saturate, the result is inf or 0 either way
+fn binary_exponent(text: &str) -> Option<i32> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, text.strip_prefix('+').unwrap_or(text))
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) { return None }
+    Some(sign * digits.chars().fold(0i32, |v, c| v.saturating_mul(10).saturating_add(c.to_digit(10).unwrap() as i32)))
 }
 
-pub fn lex(path: &String, text: String) -> Result<Vec<Token>, Error> {
-    Lexer::new(path, text).lex()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanning::tokens::reproduce;
+
+    fn round_trip(text: &str) {
+        let (tokens, eof) = lex_lossless(0, text).unwrap();
+        assert_eq!(reproduce(&tokens, &eof), text);
+    }
+    #[test]
+    fn empty() {
+        round_trip("");
+        round_trip(" \n\t");
+    }
+    #[test]
+    fn bom_and_shebang() {
+        round_trip("\u{feff}local a = 1");
+        round_trip("#!/usr/bin/env luo\nprint(1)\n");
+        round_trip("\u{feff}#!/usr/bin/env luo\r\nprint(1)");
+        round_trip("#!/usr/bin/env luo");
+        round_trip("# comment\nprint(1)");
+        assert_eq!(lex(0, "#x\n1").unwrap().iter().map(|x| x.token().clone()).collect::<Vec<_>>(), [TokenType::Integer(1)]);
+    }
+    #[test]
+    fn line_breaks() {
+        round_trip("local a = 1\r\nlocal b = 2\r\n");
+        round_trip("local a = [[\r\nx\n\ry]]\n\r");
+    }
+    #[test]
+    fn comments() {
+        round_trip("-- line\nlocal a = 1 -- trailing\n");
+        round_trip("--[==[ long\n]] still ]==] print(a) --[[x]]");
+        round_trip("--- doc\n---@param x number\nfunction f(x) end --- after");
+        round_trip("print(1) -- no line break at the end");
+    }
+    #[test]
+    fn strings() {
+        round_trip("local s = \"\\xff\\200\\z\n  x\" .. 'é' .. [=[\n]]]=]");
+    }
+    fn number(text: &str) -> Result<TokenType, ErrorKind> {
+        match lex(0, text) {
+            Ok(tokens) => Ok(tokens[0].token().clone()),
+            Err(err) => Err(err.kind().clone())
+        }
+    }
+    #[test]
+    fn numbers() {
+        assert_eq!(number("5."), Ok(TokenType::Float(5.0)));
+        assert_eq!(number(".5e-3"), Ok(TokenType::Float(0.0005)));
+        assert_eq!(number("0x.8"), Ok(TokenType::Float(0.5)));
+        assert_eq!(number("0xA.8p1"), Ok(TokenType::Float(21.0)));
+        assert_eq!(number("1e400"), Ok(TokenType::Float(f64::INFINITY)));
+        assert_eq!(number("0x1p99999999999"), Ok(TokenType::Float(f64::INFINITY)));
+        assert_eq!(number("0x1p-99999999999"), Ok(TokenType::Float(0.0)));
+        assert_eq!(number("0xffffffffffffffff"), Ok(TokenType::Integer(-1)));
+        assert_eq!(number("0x"), Err(ErrorKind::MalformedNumber("0x".to_string())));
+        assert_eq!(number("3..2"), Err(ErrorKind::MalformedNumber("3..2".to_string())));
+    }
+    #[test]
+    fn trivia_placement() {
+        let (tokens, eof) = lex_lossless(0, "-- a\nx = 1 -- b\n-- c\n").unwrap();
+        assert_eq!(tokens[0].leading().iter().map(|x| x.kind()).collect::<Vec<_>>(), [TriviaKind::LineComment, TriviaKind::Newline]);
+        assert_eq!(tokens[2].trailing().iter().map(|x| x.kind()).collect::<Vec<_>>(), [TriviaKind::Whitespace, TriviaKind::LineComment]);
+        assert_eq!(eof.iter().map(|x| x.kind()).collect::<Vec<_>>(), [TriviaKind::Newline, TriviaKind::LineComment, TriviaKind::Newline]);
+    }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 pub mod position;
+pub mod symbol;
 pub mod tokens;
 pub mod lexer;
 pub mod nodes;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod emit;
\ No newline at end of file
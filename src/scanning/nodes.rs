@@ -1,25 +1,69 @@
 use std::fmt::{Debug, Display};
 
-use super::{tokens::{TokenType, Token}, position::Position};
+use super::{tokens::{TokenType, Token}, position::Position, symbol::Symbol};
 
-pub fn join<T>(v: &Vec<T>, sep: &str) -> String where T: Display {
+pub fn join<T>(v: &[T], sep: &str) -> String where T: Display {
     v.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(sep)
 }
 
+// index of a node in its `Ast`
+pub type NodeId = usize;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeType {
-    Chunk(Vec<Node>), DoBlock(Vec<Node>), Body(Vec<Node>),
-    ID(String), Number(f64), Boolean(bool), String(String), Nil,
-    Expr(Box<Node>),
-    Binary { left: Box<Node>, op: TokenType, right: Box<Node> }, Unary { op: TokenType, node: Box<Node> },
-    Field { left: Box<Node>, right: Box<Node>, expr: bool }, Call { head: Box<Node>, args: Vec<Node> },
-    SelfCall { head: Box<Node>, field: String, args: Vec<Node> },
-    Assign(Box<Node>, Box<Node>), AssignVars(Vec<Node>, Vec<Node>),
-    LocalAssign(Box<Node>, Option<Box<Node>>), LocalAssignVars(Vec<Node>, Vec<Node>),
-    Return(Box<Node>), Break,
-    If { conds: Vec<Node>, cases: Vec<Node>, else_case: Option<Box<Node>> },
-    While { cond: Box<Node>, body: Box<Node> },
-    ForIn { vars: Vec<String>, iter: Box<Node>, body: Box<Node> }, For { var: String, start: Box<Node>, end: Box<Node>, step: Option<Box<Node>>, body: Box<Node> },
+    Chunk(Vec<NodeId>), DoBlock(Vec<NodeId>), Body(Vec<NodeId>),
+    ID(Symbol), Integer(i64), Float(f64), Boolean(bool), String(Symbol), Nil, Varargs,
+    Expr(NodeId),
+    Binary { left: NodeId, op: TokenType, right: NodeId }, Unary { op: TokenType, node: NodeId },
+    Field { left: NodeId, right: NodeId, expr: bool }, Call { head: NodeId, args: Vec<NodeId> },
+    SelfCall { head: NodeId, field: Symbol, args: Vec<NodeId> },
+    Assign(NodeId, NodeId), AssignVars(Vec<NodeId>, Vec<NodeId>),
+    LocalAssign(NodeId, Option<NodeId>), LocalAssignVars(Vec<NodeId>, Vec<NodeId>),
+    Return(Vec<NodeId>), Break, Goto(Symbol), Label(Symbol),
+    If { conds: Vec<NodeId>, cases: Vec<NodeId>, else_case: Option<NodeId> },
+    While { cond: NodeId, body: NodeId }, Repeat { body: NodeId, cond: NodeId },
+    ForIn { vars: Vec<Symbol>, iter: NodeId, body: NodeId }, For { var: Symbol, start: NodeId, end: NodeId, step: Option<NodeId>, body: NodeId },
+    Function { path: Vec<Symbol>, method: Option<Symbol>, params: Vec<NodeId>, varargs: bool, ret: Option<NodeId>, body: NodeId },
+    LocalFunction { name: Symbol, params: Vec<NodeId>, varargs: bool, ret: Option<NodeId>, body: NodeId },
+    Lambda { params: Vec<NodeId>, varargs: bool, ret: Option<NodeId>, body: NodeId },
+    Meta { name: Symbol, generics: Vec<Symbol>, funcs: Vec<NodeId> }, Implement { name: Symbol, generics: Vec<Symbol>, args: Vec<NodeId>, funcs: Vec<NodeId> },
+    Table(Vec<TableField>),
+    Var { name: Symbol, typ: Option<NodeId> },
+    TypeName { name: Symbol, args: Vec<NodeId> }, TypeFn { params: Vec<NodeId>, varargs: Option<NodeId>, ret: Option<NodeId> },
+    TypeOptional(NodeId), TypeUnion(Vec<NodeId>), TypeTuple(Vec<NodeId>),
+    // a statement that couldn't be parsed
+    Error,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableField {
+    Item(NodeId), Named(Symbol, NodeId), Keyed(NodeId, NodeId)
+}
+impl TableField {
+    pub fn format(&self, ast: &Ast, indent: usize) -> String {
+        match self {
+            Self::Item(value) => ast.format(*value, indent, false),
+            Self::Named(name, value) => format!("{name} = {}", ast.format(*value, indent, false)),
+            Self::Keyed(key, value) => format!("[{}] = {}", ast.format(*key, indent, false), ast.format(*value, indent, false)),
+        }
+    }
+    pub fn display(&self, ast: &Ast) -> String {
+        match self {
+            Self::Item(value) => format!("({})", ast.display(*value)),
+            Self::Named(name, value) => format!("{name} = ({})", ast.display(*value)),
+            Self::Keyed(key, value) => format!("[({})] = ({})", ast.display(*key), ast.display(*value)),
+        }
+    }
+}
+pub fn format_generics(generics: &[Symbol]) -> String {
+    if generics.is_empty() { String::new() } else { format!("<{}>", join(generics, ", ")) }
+}
+pub fn format_params(ast: &Ast, params: &[NodeId], varargs: bool) -> String {
+    let mut params = params.iter().map(|x| ast.display(*x).to_string()).collect::<Vec<String>>();
+    if varargs { params.push("...".to_string()) }
+    params.join(", ")
+}
+pub fn format_ret(ast: &Ast, ret: &Option<NodeId>) -> String {
+    if let Some(ret) = ret { format!(" -> {}", ast.display(*ret)) } else { String::new() }
 }
 impl NodeType {
     pub fn name(&self) -> &str {
@@ -28,11 +72,13 @@ impl NodeType {
             Self::DoBlock(_) => "do block",
             Self::Body(_) => "body",
             Self::ID(_) => "identifier",
-            Self::Number(_) => "number",
+            Self::Integer(_) => "integer",
+            Self::Float(_) => "float",
             Self::Boolean(_) => "boolean",
             Self::String(_) => "string",
             Self::Nil => "nil",
-            Self::Expr(n) => n.node.name(),
+            Self::Varargs => "varargs",
+            Self::Expr(_) => "parenthesized expression",
             Self::Binary { left:_, op:_, right:_ } => "binary operation",
             Self::Unary { op:_, node:_ } => "unary operation",
             Self::Field { left:_, right:_, expr:_ } => "field operation",
@@ -44,147 +90,823 @@ impl NodeType {
             Self::LocalAssignVars(_, _) => "local assignments",
             Self::Return(_) => "return statement",
             Self::Break => "break statement",
+            Self::Goto(_) => "goto statement",
+            Self::Label(_) => "label",
             Self::If { conds:_, cases:_, else_case:_ } => "if statement",
             Self::While { cond:_, body:_ } => "while statement",
+            Self::Repeat { body:_, cond:_ } => "repeat statement",
             Self::ForIn { vars:_, iter:_, body:_ } => "for-in statement",
             Self::For { var:_, start:_, end:_, step:_, body:_ } => "for statement",
+            Self::Function { path:_, method:_, params:_, varargs:_, ret:_, body:_ } => "function definition",
+            Self::LocalFunction { name:_, params:_, varargs:_, ret:_, body:_ } => "local function definition",
+            Self::Lambda { params:_, varargs:_, ret:_, body:_ } => "function",
+            Self::Table(_) => "table",
+            Self::Meta { name:_, generics:_, funcs:_ } => "meta definition",
+            Self::Implement { name:_, generics:_, args:_, funcs:_ } => "implementation",
+            Self::Var { name:_, typ:_ } => "variable",
+            Self::TypeName { name:_, args:_ } | Self::TypeFn { params:_, varargs:_, ret:_ } |
+            Self::TypeOptional(_) | Self::TypeUnion(_) | Self::TypeTuple(_) => "type",
+            Self::Error => "error",
         }
     }
-    pub fn format(&self, indent: usize, stat: bool) -> String {
+    pub fn format(&self, ast: &Ast, indent: usize, stat: bool) -> String {
         let prefix = "\t".repeat(indent);
+        let expr = |id: &NodeId| ast.format(*id, indent, false);
+        let exprs = |ids: &[NodeId]| ids.iter().map(|x| ast.format(*x, indent, false)).collect::<Vec<String>>().join(", ");
+        let block = |ids: &[NodeId]| ids.iter().map(|x| ast.format(*x, indent + 1, true)).collect::<Vec<String>>().join("\n");
+        let body = |id: &NodeId| ast.format(*id, indent, true);
         match self {
-            Self::Chunk(nodes) => format!("{}",
-            nodes.iter().map(|x| x.format(indent, true)).collect::<Vec<String>>().join("\n")),
+            Self::Chunk(nodes) => nodes.iter().map(|x| ast.format(*x, indent, true)).collect::<Vec<String>>().join("\n"),
+            Self::DoBlock(nodes) => format!("{prefix}do\n{}\n{prefix}end", block(nodes)),
+            Self::Body(nodes) => format!("\n{}", block(nodes)),
 
-            Self::DoBlock(nodes) => format!("{prefix}do\n{}\n{prefix}end",
-            nodes.iter().map(|x| x.format(indent + 1, true)).collect::<Vec<String>>().join("\n")),
-            
-            Self::Body(nodes) => format!("\n{}",
-            nodes.iter().map(|x| x.format(indent + 1, true)).collect::<Vec<String>>().join("\n")),
-
-            Self::ID(v) => format!("{v}"),
-            Self::Number(v) => format!("{v}"),
+            Self::ID(v) => v.to_string(),
+            Self::Integer(v) => format!("{v}"),
+            Self::Float(v) => format!("{v:?}"),
             Self::Boolean(v) => format!("{v}"),
             Self::String(v) => format!("{v:?}"),
-            Self::Nil => format!("nil"),
-            Self::Expr(n) => format!("{}",
-            n.format(indent, false)),
-
-            Self::Binary { left, op, right } => format!("{} {} {}",
-            left.format(indent, false), op.display(), right.format(indent, false)),
+            Self::Nil => "nil".to_string(),
+            Self::Varargs => "...".to_string(),
+            Self::Expr(n) => format!("({})", expr(n)),
 
-            Self::Unary { op, node } => format!("{}{}",
-            op.display(), node.format(indent, false)),
+            Self::Binary { left, op, right } => format!("{} {} {}", expr(left), op.display(), expr(right)),
 
-            Self::Field { left, right, expr } => if *expr {
-                format!("{}[{}]",
-                left.format(indent, false), right.format(indent, false))
-            } else {
-                format!("{}.{}", left.format(indent, false), right.format(indent, false))
+            Self::Unary { op, node } => {
+                let code = expr(node);
+                // keep `not x` a word and `- -x` from reading as a comment
+                format!("{}{}{code}", op.display(), if *op == TokenType::Not || code.starts_with('-') { " " } else { "" })
             }
 
-            Self::Call { head, args } => if stat {
-                format!("{prefix}{}({})", head.format(indent, false), args.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", "))
-            } else {
-                format!("{}({})", head.format(indent, false), args.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", "))
-            },
-            Self::SelfCall { head, field, args } => if stat {
-                format!("{prefix}{}:{field}({})", head.format(indent, false), args.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", "))
-            } else {
-                format!("{}:{field}({})", head.format(indent, false), args.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", "))
-            },
-
-            Self::Assign(id, expr) => format!("{prefix}{} = {}",
-            id.format(indent, false), expr.format(indent, false)),
-
-            Self::AssignVars(ids, exprs) => format!("{prefix}{} = {}",
-            ids.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", "), exprs.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", ")),
+            Self::Field { left, right, expr: true } => format!("{}[{}]", expr(left), expr(right)),
+            Self::Field { left, right, expr: false } => format!("{}.{}", expr(left), expr(right)),
 
-            Self::LocalAssign(id, expr) => format!("{prefix}local {}{}",
-            id.format(indent, false), if let Some(expr) = expr { format!(" = {}", expr.format(indent, false)) } else { "".to_string() }),
+            Self::Call { head, args } => format!("{}{}({})", if stat { prefix.as_str() } else { "" }, expr(head), exprs(args)),
+            Self::SelfCall { head, field, args } => format!("{}{}:{field}({})", if stat { prefix.as_str() } else { "" }, expr(head), exprs(args)),
 
-            Self::LocalAssignVars(ids, exprs) => format!("{prefix}local {} = {}",
-            ids.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", "), exprs.iter().map(|x| x.format(indent, false)).collect::<Vec<String>>().join(", ")),
+            Self::Assign(id, value) => format!("{prefix}{} = {}", expr(id), expr(value)),
+            Self::AssignVars(ids, values) => format!("{prefix}{} = {}", exprs(ids), exprs(values)),
+            Self::LocalAssign(id, value) => format!("{prefix}local {}{}",
+            expr(id), if let Some(value) = value { format!(" = {}", expr(value)) } else { "".to_string() }),
+            Self::LocalAssignVars(ids, values) => format!("{prefix}local {}{}",
+            exprs(ids), if values.is_empty() { "".to_string() } else { format!(" = {}", exprs(values)) }),
 
-            Self::Return(v) => format!("{prefix}return {}",
-            v.format(indent, false)),
+            Self::Return(values) => format!("{prefix}return{}", values.iter().map(|x| format!(" {}", expr(x))).collect::<Vec<String>>().join(",")),
 
             Self::Break => format!("{prefix}break"),
+            Self::Goto(name) => format!("{prefix}goto {name}"),
+            Self::Label(name) => format!("{prefix}::{name}::"),
 
             Self::If { conds, cases, else_case } =>
                 format!("{prefix}if {}{}\n{prefix}end",
                 conds.iter().enumerate().map(|(i, cond)|
-                    format!("{} then{}", cond.format(indent, false), cases[i].format(indent, true))
+                    format!("{} then{}", expr(cond), body(&cases[i]))
                 ).collect::<Vec<String>>().join(format!("\n{prefix}elseif ").as_str()),
-                if let Some(else_case) = else_case { format!("\n{prefix}else {}", else_case.format(indent, true)) } else { "".to_string() }),
-
-            Self::While { cond, body } => format!("{prefix}while {} do {}\n{prefix}end",
-            cond.format(indent, false), body.format(indent, true)),
-            
-            Self::ForIn { vars, iter, body } => format!("{prefix}for {} in {} do {}\n{prefix}end",
-            join(vars, ", "), iter.format(indent, false), body.format(indent, true)),
-            
-            Self::For { var, start, end, step, body } => format!("{prefix}for {var} = {}, {}{} do {}\n{prefix}end",
-            start.format(indent, false), end.format(indent, false),
-            if let Some(step) = step { format!(", {}", step.format(indent, false)) } else { "".to_string() },
-            body.format(indent, true)),
-            
+                if let Some(else_case) = else_case { format!("\n{prefix}else {}", body(else_case)) } else { "".to_string() }),
+
+            Self::While { cond, body: block } => format!("{prefix}while {} do {}\n{prefix}end", expr(cond), body(block)),
+            Self::Repeat { body: block, cond } => format!("{prefix}repeat {}\n{prefix}until {}", body(block), expr(cond)),
+            Self::ForIn { vars, iter, body: block } => format!("{prefix}for {} in {} do {}\n{prefix}end", join(vars, ", "), expr(iter), body(block)),
+            Self::For { var, start, end, step, body: block } => format!("{prefix}for {var} = {}, {}{} do {}\n{prefix}end",
+            expr(start), expr(end), if let Some(step) = step { format!(", {}", expr(step)) } else { "".to_string() }, body(block)),
+
+            Self::Function { path, method, params, varargs, ret, body: block } => format!("{prefix}function {}{}({}){} {}\n{prefix}end",
+            join(path, "."), if let Some(method) = method { format!(":{method}") } else { String::new() },
+            format_params(ast, params, *varargs), format_ret(ast, ret), body(block)),
+
+            Self::LocalFunction { name, params, varargs, ret, body: block } => format!("{prefix}local function {name}({}){} {}\n{prefix}end",
+            format_params(ast, params, *varargs), format_ret(ast, ret), body(block)),
+
+            Self::Lambda { params, varargs, ret, body: block } => format!("function({}){} {}\n{prefix}end",
+            format_params(ast, params, *varargs), format_ret(ast, ret), body(block)),
+
+            Self::Meta { name, generics, funcs } => format!("{prefix}meta{} {name}\n{}\n{prefix}end", format_generics(generics), block(funcs)),
+
+            Self::Implement { name, generics, args, funcs } => format!("{prefix}implement{} {name}{}\n{}\n{prefix}end",
+            format_generics(generics), if args.is_empty() { String::new() } else { format!("<{}>", args.iter().map(|x| ast.display(*x).to_string()).collect::<Vec<String>>().join(", ")) },
+            block(funcs)),
+
+            Self::Table(fields) => format!("{{{}}}", fields.iter().map(|x| x.format(ast, indent)).collect::<Vec<String>>().join(", ")),
+
+            Self::Var { name:_, typ:_ } | Self::TypeName { name:_, args:_ } | Self::TypeFn { params:_, varargs:_, ret:_ } |
+            Self::TypeOptional(_) | Self::TypeUnion(_) | Self::TypeTuple(_) => NodeDisplay { ast, node: self }.to_string(),
+            Self::Error => format!("{prefix}<error>"),
         }
     }
 }
-impl Display for NodeType {
+// a node on a single line with every child in parentheses, made by `Ast::display`
+pub struct NodeDisplay<'a> {
+    ast: &'a Ast,
+    node: &'a NodeType
+}
+impl Display for NodeDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Chunk(nodes) => write!(f, "\n{}\n", join(nodes, "\n")),
-            Self::DoBlock(nodes) => write!(f, "do {} end", join(nodes, " ")),
-            Self::Body(nodes) => write!(f, "{}", join(nodes, " ")),
-            Self::ID(v) => write!(f, "{v}"),
-            Self::Number(v) => write!(f, "{v}"),
-            Self::Boolean(v) => write!(f, "{v}"),
-            Self::String(v) => write!(f, "{v:?}"),
-            Self::Nil => write!(f, "nil"),
-            Self::Expr(n) => write!(f, "{n}"),
-            Self::Binary { left, op, right } => write!(f, "{left} {} {right}", op.display()),
-            Self::Unary { op, node } => write!(f, "{} {node}", op.display()),
-            Self::Field { left, right, expr:_ } => write!(f, "{left} . {right}"),
-            Self::Call { head, args } => write!(f, "{head}({})", join(args, ", ")),
-            Self::SelfCall { head, field, args } => write!(f, "{head}:{field}({})", join(args, ", ")),
-            Self::Assign(id, expr) => write!(f, "{id} = {expr}"),
-            Self::AssignVars(ids, exprs) => write!(f, "{} = {}", join(ids, ", "), join(exprs, ", ")),
-            Self::LocalAssign(id, expr) => write!(f, "local {id}{}", if let Some(expr) = expr { format!(" = {expr}") } else { "".to_string() }),
-            Self::LocalAssignVars(ids, exprs) => write!(f, "local {} = {}", join(ids, ", "), join(exprs, ", ")),
-            Self::Return(v) => write!(f, "return {v}"),
-            Self::Break => write!(f, "break"),
-            Self::If { conds, cases, else_case } => write!(f, "if {}{} end",
-            conds.iter().enumerate().map(|(i, cond)|format!("{cond} then {}", cases[i])).collect::<Vec<String>>().join(" elseif "),
-            if let Some(else_case) = else_case { format!(" else {else_case}") } else { "".to_string() }),
-            Self::While { cond, body } => write!(f, "while {cond} do {body} end"),
-            Self::ForIn { vars, iter, body } => write!(f, "for {} in {iter} do {body} end", join(vars, ", ")),
-            Self::For { var, start, end, step, body } => write!(f, "for {var} = {start}, {end}{} do {body} end",
-            if let Some(step) = step { format!(", {step}") } else { "".to_string() }),
+        let ast = self.ast;
+        let node = |id: &NodeId| format!("({})", ast.display(*id));
+        let nodes = |ids: &[NodeId], sep: &str| ids.iter().map(node).collect::<Vec<String>>().join(sep);
+        let typ = |id: &NodeId| ast.display(*id).to_string();
+        let types = |ids: &[NodeId], sep: &str| ids.iter().map(typ).collect::<Vec<String>>().join(sep);
+        match self.node {
+            NodeType::Chunk(ids) => write!(f, "\n{}\n", nodes(ids, "\n")),
+            NodeType::DoBlock(ids) => write!(f, "do {} end", nodes(ids, " ")),
+            NodeType::Body(ids) => write!(f, "{}", nodes(ids, " ")),
+            NodeType::ID(v) => write!(f, "{v}"),
+            NodeType::Integer(v) => write!(f, "{v}"),
+            NodeType::Float(v) => write!(f, "{v:?}"),
+            NodeType::Boolean(v) => write!(f, "{v}"),
+            NodeType::String(v) => write!(f, "{v:?}"),
+            NodeType::Nil => write!(f, "nil"),
+            NodeType::Varargs => write!(f, "..."),
+            NodeType::Expr(n) => write!(f, "{}", node(n)),
+            NodeType::Binary { left, op, right } => write!(f, "{} {} {}", node(left), op.display(), node(right)),
+            NodeType::Unary { op, node: operand } => write!(f, "{} {}", op.display(), node(operand)),
+            NodeType::Field { left, right, expr:_ } => write!(f, "{} . {}", node(left), node(right)),
+            NodeType::Call { head, args } => write!(f, "{}({})", node(head), nodes(args, ", ")),
+            NodeType::SelfCall { head, field, args } => write!(f, "{}:{field}({})", node(head), nodes(args, ", ")),
+            NodeType::Assign(id, expr) => write!(f, "{} = {}", node(id), node(expr)),
+            NodeType::AssignVars(ids, exprs) => write!(f, "{} = {}", nodes(ids, ", "), nodes(exprs, ", ")),
+            NodeType::LocalAssign(id, expr) => write!(f, "local {}{}", node(id), if let Some(expr) = expr { format!(" = {}", node(expr)) } else { "".to_string() }),
+            NodeType::LocalAssignVars(ids, exprs) => write!(f, "local {}{}", nodes(ids, ", "),
+            if exprs.is_empty() { "".to_string() } else { format!(" = {}", nodes(exprs, ", ")) }),
+            NodeType::Return(exprs) => if exprs.is_empty() { write!(f, "return") } else { write!(f, "return {}", nodes(exprs, ", ")) }
+            NodeType::Break => write!(f, "break"),
+            NodeType::Goto(name) => write!(f, "goto {name}"),
+            NodeType::Label(name) => write!(f, "::{name}::"),
+            NodeType::If { conds, cases, else_case } => write!(f, "if {}{} end",
+            conds.iter().enumerate().map(|(i, cond)| format!("{} then {}", node(cond), node(&cases[i]))).collect::<Vec<String>>().join(" elseif "),
+            if let Some(else_case) = else_case { format!(" else {}", node(else_case)) } else { "".to_string() }),
+            NodeType::While { cond, body } => write!(f, "while {} do {} end", node(cond), node(body)),
+            NodeType::Repeat { body, cond } => write!(f, "repeat {} until {}", node(body), node(cond)),
+            NodeType::ForIn { vars, iter, body } => write!(f, "for {} in {} do {} end", join(vars, ", "), node(iter), node(body)),
+            NodeType::For { var, start, end, step, body } => write!(f, "for {var} = {}, {}{} do {} end", node(start), node(end),
+            if let Some(step) = step { format!(", {}", node(step)) } else { "".to_string() }, node(body)),
+            NodeType::Function { path, method, params, varargs, ret, body } => write!(f, "function {}{}({}){} {} end",
+            join(path, "."), if let Some(method) = method { format!(":{method}") } else { String::new() },
+            format_params(ast, params, *varargs), format_ret(ast, ret), node(body)),
+            NodeType::LocalFunction { name, params, varargs, ret, body } => write!(f, "local function {name}({}){} {} end",
+            format_params(ast, params, *varargs), format_ret(ast, ret), node(body)),
+            NodeType::Lambda { params, varargs, ret, body } => write!(f, "function({}){} {} end",
+            format_params(ast, params, *varargs), format_ret(ast, ret), node(body)),
+            NodeType::Table(fields) => write!(f, "{{{}}}", fields.iter().map(|x| x.display(ast)).collect::<Vec<String>>().join(", ")),
+            NodeType::Meta { name, generics, funcs } => write!(f, "meta{} {name} {} end", format_generics(generics), nodes(funcs, " ")),
+            NodeType::Implement { name, generics, args, funcs } => write!(f, "implement{} {name}{} {} end", format_generics(generics),
+            if args.is_empty() { String::new() } else { format!("<{}>", types(args, ", ")) },
+            nodes(funcs, " ")),
+            NodeType::Var { name, typ: var_type } => write!(f, "{name}{}", if let Some(var_type) = var_type { format!(": {}", typ(var_type)) } else { "".to_string() }),
+            NodeType::TypeName { name, args } => if args.is_empty() {
+                write!(f, "{name}")
+            } else {
+                write!(f, "{name}<{}>", types(args, ", "))
+            }
+            NodeType::TypeFn { params, varargs, ret } => {
+                let mut params = params.iter().map(typ).collect::<Vec<String>>();
+                if let Some(varargs) = varargs { params.push(format!("...{}", typ(varargs))) }
+                write!(f, "function({}){}", params.join(", "), format_ret(ast, ret))
+            }
+            NodeType::TypeOptional(inner) => write!(f, "{}?", typ(inner)),
+            NodeType::TypeUnion(ids) => write!(f, "{}", types(ids, " | ")),
+            NodeType::TypeTuple(ids) => write!(f, "({})", types(ids, ", ")),
+            NodeType::Error => write!(f, "<error>"),
         }
     }
 }
 #[derive(Clone, PartialEq)]
 pub struct Node {
+    id: NodeId,
     node: NodeType,
     pos: Position
 }
 impl Node {
-    pub fn new(node: NodeType, pos: Position) -> Self { Self { node, pos } }
+    // the id is given when the node is added to an `Ast`
+    pub fn new(node: NodeType, pos: Position) -> Self { Self { id: 0, node, pos } }
+    pub fn id(&self) -> NodeId { self.id }
     pub fn node(&self) -> &NodeType { &self.node }
     pub fn pos(&self) -> &Position { &self.pos }
-    pub fn format(&self, indent: usize, stat: bool) -> String {
-        self.node.format(indent, stat)
-    }
 }
 impl Debug for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self.node)
     }
 }
-impl Display for Node {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "({})", self.node)
+
+// owns every node of a tree, nodes refer to their children by id so passes can keep side tables keyed by it
+#[derive(Debug, Clone, Default)]
+pub struct Ast {
+    nodes: Vec<Node>,
+    root: NodeId
+}
+impl Ast {
+    pub fn add(&mut self, node: NodeType, pos: Position) -> NodeId {
+        self.nodes.push(Node { id: self.nodes.len(), node, pos });
+        self.nodes.len() - 1
+    }
+    pub fn get(&self, id: NodeId) -> &Node { &self.nodes[id] }
+    pub fn get_mut(&mut self, id: NodeId) -> &mut Node { &mut self.nodes[id] }
+    pub fn pos(&self, id: NodeId) -> &Position { &self.nodes[id].pos }
+    // puts a node in the place of another one, it takes over the id
+    pub fn replace(&mut self, id: NodeId, mut node: Node) -> Node {
+        node.id = id;
+        std::mem::replace(&mut self.nodes[id], node)
+    }
+    pub fn root(&self) -> NodeId { self.root }
+    pub fn set_root(&mut self, id: NodeId) { self.root = id }
+    pub fn format(&self, id: NodeId, indent: usize, stat: bool) -> String {
+        self.nodes[id].node.format(self, indent, stat)
+    }
+    pub fn display(&self, id: NodeId) -> NodeDisplay<'_> {
+        NodeDisplay { ast: self, node: &self.nodes[id].node }
+    }
+}
+
+// walks the tree without changing it, every method defaults to visiting the children of its node
+// passes override the nodes they care about and call `visit_node` on the children they still want walked
+pub trait Visitor {
+    fn visit_node(&mut self, ast: &Ast, id: NodeId) {
+        let node = ast.get(id);
+        let pos = &node.pos;
+        match &node.node {
+            NodeType::Chunk(nodes) => self.visit_chunk(ast, nodes, pos),
+            NodeType::DoBlock(nodes) => self.visit_do_block(ast, nodes, pos),
+            NodeType::Body(nodes) => self.visit_body(ast, nodes, pos),
+            NodeType::ID(id) => self.visit_id(ast, *id, pos),
+            NodeType::Integer(value) => self.visit_integer(ast, *value, pos),
+            NodeType::Float(value) => self.visit_float(ast, *value, pos),
+            NodeType::Boolean(value) => self.visit_boolean(ast, *value, pos),
+            NodeType::String(value) => self.visit_string(ast, *value, pos),
+            NodeType::Nil => self.visit_nil(ast, pos),
+            NodeType::Varargs => self.visit_varargs(ast, pos),
+            NodeType::Expr(node) => self.visit_expr(ast, *node, pos),
+            NodeType::Binary { left, op, right } => self.visit_binary(ast, *left, op, *right, pos),
+            NodeType::Unary { op, node } => self.visit_unary(ast, op, *node, pos),
+            NodeType::Field { left, right, expr } => self.visit_field(ast, *left, *right, *expr, pos),
+            NodeType::Call { head, args } => self.visit_call(ast, *head, args, pos),
+            NodeType::SelfCall { head, field, args } => self.visit_self_call(ast, *head, *field, args, pos),
+            NodeType::Assign(var, expr) => self.visit_assign(ast, *var, *expr, pos),
+            NodeType::AssignVars(vars, exprs) => self.visit_assign_vars(ast, vars, exprs, pos),
+            NodeType::LocalAssign(var, expr) => self.visit_local_assign(ast, *var, *expr, pos),
+            NodeType::LocalAssignVars(vars, exprs) => self.visit_local_assign_vars(ast, vars, exprs, pos),
+            NodeType::Return(exprs) => self.visit_return(ast, exprs, pos),
+            NodeType::Break => self.visit_break(ast, pos),
+            NodeType::Goto(name) => self.visit_goto(ast, *name, pos),
+            NodeType::Label(name) => self.visit_label(ast, *name, pos),
+            NodeType::If { conds, cases, else_case } => self.visit_if(ast, conds, cases, *else_case, pos),
+            NodeType::While { cond, body } => self.visit_while(ast, *cond, *body, pos),
+            NodeType::Repeat { body, cond } => self.visit_repeat(ast, *body, *cond, pos),
+            NodeType::ForIn { vars, iter, body } => self.visit_for_in(ast, vars, *iter, *body, pos),
+            NodeType::For { var, start, end, step, body } => self.visit_for(ast, *var, *start, *end, *step, *body, pos),
+            NodeType::Function { path, method, params, varargs, ret, body } => self.visit_function(ast, path, *method, params, *varargs, *ret, *body, pos),
+            NodeType::LocalFunction { name, params, varargs, ret, body } => self.visit_local_function(ast, *name, params, *varargs, *ret, *body, pos),
+            NodeType::Lambda { params, varargs, ret, body } => self.visit_lambda(ast, params, *varargs, *ret, *body, pos),
+            NodeType::Meta { name, generics, funcs } => self.visit_meta(ast, *name, generics, funcs, pos),
+            NodeType::Implement { name, generics, args, funcs } => self.visit_implement(ast, *name, generics, args, funcs, pos),
+            NodeType::Table(fields) => self.visit_table(ast, fields, pos),
+            NodeType::Var { name, typ } => self.visit_var(ast, *name, *typ, pos),
+            NodeType::TypeName { name, args } => self.visit_type_name(ast, *name, args, pos),
+            NodeType::TypeFn { params, varargs, ret } => self.visit_type_fn(ast, params, *varargs, *ret, pos),
+            NodeType::TypeOptional(typ) => self.visit_type_optional(ast, *typ, pos),
+            NodeType::TypeUnion(types) => self.visit_type_union(ast, types, pos),
+            NodeType::TypeTuple(types) => self.visit_type_tuple(ast, types, pos),
+            NodeType::Error => self.visit_error(ast, pos),
+        }
+    }
+    fn visit_nodes(&mut self, ast: &Ast, ids: &[NodeId]) {
+        for &id in ids { self.visit_node(ast, id) }
+    }
+    fn visit_table_field(&mut self, ast: &Ast, field: &TableField) {
+        match field {
+            TableField::Item(value) | TableField::Named(_, value) => self.visit_node(ast, *value),
+            TableField::Keyed(key, value) => { self.visit_node(ast, *key); self.visit_node(ast, *value) }
+        }
+    }
+    fn visit_chunk(&mut self, ast: &Ast, nodes: &[NodeId], pos: &Position) { self.visit_nodes(ast, nodes) }
+    fn visit_do_block(&mut self, ast: &Ast, nodes: &[NodeId], pos: &Position) { self.visit_nodes(ast, nodes) }
+    fn visit_body(&mut self, ast: &Ast, nodes: &[NodeId], pos: &Position) { self.visit_nodes(ast, nodes) }
+    fn visit_id(&mut self, ast: &Ast, id: Symbol, pos: &Position) {}
+    fn visit_integer(&mut self, ast: &Ast, value: i64, pos: &Position) {}
+    fn visit_float(&mut self, ast: &Ast, value: f64, pos: &Position) {}
+    fn visit_boolean(&mut self, ast: &Ast, value: bool, pos: &Position) {}
+    fn visit_string(&mut self, ast: &Ast, value: Symbol, pos: &Position) {}
+    fn visit_nil(&mut self, ast: &Ast, pos: &Position) {}
+    fn visit_varargs(&mut self, ast: &Ast, pos: &Position) {}
+    fn visit_expr(&mut self, ast: &Ast, node: NodeId, pos: &Position) { self.visit_node(ast, node) }
+    fn visit_binary(&mut self, ast: &Ast, left: NodeId, op: &TokenType, right: NodeId, pos: &Position) {
+        self.visit_node(ast, left);
+        self.visit_node(ast, right);
+    }
+    fn visit_unary(&mut self, ast: &Ast, op: &TokenType, node: NodeId, pos: &Position) { self.visit_node(ast, node) }
+    fn visit_field(&mut self, ast: &Ast, left: NodeId, right: NodeId, expr: bool, pos: &Position) {
+        self.visit_node(ast, left);
+        self.visit_node(ast, right);
+    }
+    fn visit_call(&mut self, ast: &Ast, head: NodeId, args: &[NodeId], pos: &Position) {
+        self.visit_node(ast, head);
+        self.visit_nodes(ast, args);
+    }
+    fn visit_self_call(&mut self, ast: &Ast, head: NodeId, field: Symbol, args: &[NodeId], pos: &Position) {
+        self.visit_node(ast, head);
+        self.visit_nodes(ast, args);
+    }
+    fn visit_assign(&mut self, ast: &Ast, var: NodeId, expr: NodeId, pos: &Position) {
+        self.visit_node(ast, var);
+        self.visit_node(ast, expr);
+    }
+    fn visit_assign_vars(&mut self, ast: &Ast, vars: &[NodeId], exprs: &[NodeId], pos: &Position) {
+        self.visit_nodes(ast, vars);
+        self.visit_nodes(ast, exprs);
+    }
+    fn visit_local_assign(&mut self, ast: &Ast, var: NodeId, expr: Option<NodeId>, pos: &Position) {
+        self.visit_node(ast, var);
+        if let Some(expr) = expr { self.visit_node(ast, expr) }
+    }
+    fn visit_local_assign_vars(&mut self, ast: &Ast, vars: &[NodeId], exprs: &[NodeId], pos: &Position) {
+        self.visit_nodes(ast, vars);
+        self.visit_nodes(ast, exprs);
+    }
+    fn visit_return(&mut self, ast: &Ast, exprs: &[NodeId], pos: &Position) { self.visit_nodes(ast, exprs) }
+    fn visit_break(&mut self, ast: &Ast, pos: &Position) {}
+    fn visit_goto(&mut self, ast: &Ast, name: Symbol, pos: &Position) {}
+    fn visit_label(&mut self, ast: &Ast, name: Symbol, pos: &Position) {}
+    fn visit_if(&mut self, ast: &Ast, conds: &[NodeId], cases: &[NodeId], else_case: Option<NodeId>, pos: &Position) {
+        self.visit_nodes(ast, conds);
+        self.visit_nodes(ast, cases);
+        if let Some(else_case) = else_case { self.visit_node(ast, else_case) }
+    }
+    fn visit_while(&mut self, ast: &Ast, cond: NodeId, body: NodeId, pos: &Position) {
+        self.visit_node(ast, cond);
+        self.visit_node(ast, body);
+    }
+    fn visit_repeat(&mut self, ast: &Ast, body: NodeId, cond: NodeId, pos: &Position) {
+        self.visit_node(ast, body);
+        self.visit_node(ast, cond);
+    }
+    fn visit_for_in(&mut self, ast: &Ast, vars: &[Symbol], iter: NodeId, body: NodeId, pos: &Position) {
+        self.visit_node(ast, iter);
+        self.visit_node(ast, body);
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn visit_for(&mut self, ast: &Ast, var: Symbol, start: NodeId, end: NodeId, step: Option<NodeId>, body: NodeId, pos: &Position) {
+        self.visit_node(ast, start);
+        self.visit_node(ast, end);
+        if let Some(step) = step { self.visit_node(ast, step) }
+        self.visit_node(ast, body);
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn visit_function(&mut self, ast: &Ast, path: &[Symbol], method: Option<Symbol>, params: &[NodeId], varargs: bool, ret: Option<NodeId>, body: NodeId, pos: &Position) {
+        self.visit_nodes(ast, params);
+        if let Some(ret) = ret { self.visit_node(ast, ret) }
+        self.visit_node(ast, body);
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn visit_local_function(&mut self, ast: &Ast, name: Symbol, params: &[NodeId], varargs: bool, ret: Option<NodeId>, body: NodeId, pos: &Position) {
+        self.visit_nodes(ast, params);
+        if let Some(ret) = ret { self.visit_node(ast, ret) }
+        self.visit_node(ast, body);
+    }
+    fn visit_lambda(&mut self, ast: &Ast, params: &[NodeId], varargs: bool, ret: Option<NodeId>, body: NodeId, pos: &Position) {
+        self.visit_nodes(ast, params);
+        if let Some(ret) = ret { self.visit_node(ast, ret) }
+        self.visit_node(ast, body);
+    }
+    fn visit_meta(&mut self, ast: &Ast, name: Symbol, generics: &[Symbol], funcs: &[NodeId], pos: &Position) { self.visit_nodes(ast, funcs) }
+    fn visit_implement(&mut self, ast: &Ast, name: Symbol, generics: &[Symbol], args: &[NodeId], funcs: &[NodeId], pos: &Position) {
+        self.visit_nodes(ast, args);
+        self.visit_nodes(ast, funcs);
+    }
+    fn visit_table(&mut self, ast: &Ast, fields: &[TableField], pos: &Position) { for field in fields { self.visit_table_field(ast, field) } }
+    fn visit_var(&mut self, ast: &Ast, name: Symbol, typ: Option<NodeId>, pos: &Position) { if let Some(typ) = typ { self.visit_node(ast, typ) } }
+    fn visit_type_name(&mut self, ast: &Ast, name: Symbol, args: &[NodeId], pos: &Position) { self.visit_nodes(ast, args) }
+    fn visit_type_fn(&mut self, ast: &Ast, params: &[NodeId], varargs: Option<NodeId>, ret: Option<NodeId>, pos: &Position) {
+        self.visit_nodes(ast, params);
+        if let Some(varargs) = varargs { self.visit_node(ast, varargs) }
+        if let Some(ret) = ret { self.visit_node(ast, ret) }
+    }
+    fn visit_type_optional(&mut self, ast: &Ast, typ: NodeId, pos: &Position) { self.visit_node(ast, typ) }
+    fn visit_type_union(&mut self, ast: &Ast, types: &[NodeId], pos: &Position) { self.visit_nodes(ast, types) }
+    fn visit_type_tuple(&mut self, ast: &Ast, types: &[NodeId], pos: &Position) { self.visit_nodes(ast, types) }
+    fn visit_error(&mut self, ast: &Ast, pos: &Position) {}
+}
+
+// walks the tree changing it in place, same as `Visitor` otherwise
+pub trait VisitorMut {
+    fn visit_node_mut(&mut self, ast: &mut Ast, id: NodeId) {
+        // the node is taken out of the tree while it's visited, so both can be changed
+        let placeholder = Node::new(NodeType::Error, ast.pos(id).clone());
+        let mut node = ast.replace(id, placeholder);
+        let pos = &mut node.pos;
+        match &mut node.node {
+            NodeType::Chunk(nodes) => self.visit_chunk_mut(ast, nodes, pos),
+            NodeType::DoBlock(nodes) => self.visit_do_block_mut(ast, nodes, pos),
+            NodeType::Body(nodes) => self.visit_body_mut(ast, nodes, pos),
+            NodeType::ID(id) => self.visit_id_mut(ast, id, pos),
+            NodeType::Integer(value) => self.visit_integer_mut(ast, value, pos),
+            NodeType::Float(value) => self.visit_float_mut(ast, value, pos),
+            NodeType::Boolean(value) => self.visit_boolean_mut(ast, value, pos),
+            NodeType::String(value) => self.visit_string_mut(ast, value, pos),
+            NodeType::Nil => self.visit_nil_mut(ast, pos),
+            NodeType::Varargs => self.visit_varargs_mut(ast, pos),
+            NodeType::Expr(node) => self.visit_expr_mut(ast, node, pos),
+            NodeType::Binary { left, op, right } => self.visit_binary_mut(ast, left, op, right, pos),
+            NodeType::Unary { op, node } => self.visit_unary_mut(ast, op, node, pos),
+            NodeType::Field { left, right, expr } => self.visit_field_mut(ast, left, right, expr, pos),
+            NodeType::Call { head, args } => self.visit_call_mut(ast, head, args, pos),
+            NodeType::SelfCall { head, field, args } => self.visit_self_call_mut(ast, head, field, args, pos),
+            NodeType::Assign(var, expr) => self.visit_assign_mut(ast, var, expr, pos),
+            NodeType::AssignVars(vars, exprs) => self.visit_assign_vars_mut(ast, vars, exprs, pos),
+            NodeType::LocalAssign(var, expr) => self.visit_local_assign_mut(ast, var, expr, pos),
+            NodeType::LocalAssignVars(vars, exprs) => self.visit_local_assign_vars_mut(ast, vars, exprs, pos),
+            NodeType::Return(exprs) => self.visit_return_mut(ast, exprs, pos),
+            NodeType::Break => self.visit_break_mut(ast, pos),
+            NodeType::Goto(name) => self.visit_goto_mut(ast, name, pos),
+            NodeType::Label(name) => self.visit_label_mut(ast, name, pos),
+            NodeType::If { conds, cases, else_case } => self.visit_if_mut(ast, conds, cases, else_case, pos),
+            NodeType::While { cond, body } => self.visit_while_mut(ast, cond, body, pos),
+            NodeType::Repeat { body, cond } => self.visit_repeat_mut(ast, body, cond, pos),
+            NodeType::ForIn { vars, iter, body } => self.visit_for_in_mut(ast, vars, iter, body, pos),
+            NodeType::For { var, start, end, step, body } => self.visit_for_mut(ast, var, start, end, step, body, pos),
+            NodeType::Function { path, method, params, varargs, ret, body } => self.visit_function_mut(ast, path, method, params, varargs, ret, body, pos),
+            NodeType::LocalFunction { name, params, varargs, ret, body } => self.visit_local_function_mut(ast, name, params, varargs, ret, body, pos),
+            NodeType::Lambda { params, varargs, ret, body } => self.visit_lambda_mut(ast, params, varargs, ret, body, pos),
+            NodeType::Meta { name, generics, funcs } => self.visit_meta_mut(ast, name, generics, funcs, pos),
+            NodeType::Implement { name, generics, args, funcs } => self.visit_implement_mut(ast, name, generics, args, funcs, pos),
+            NodeType::Table(fields) => self.visit_table_mut(ast, fields, pos),
+            NodeType::Var { name, typ } => self.visit_var_mut(ast, name, typ, pos),
+            NodeType::TypeName { name, args } => self.visit_type_name_mut(ast, name, args, pos),
+            NodeType::TypeFn { params, varargs, ret } => self.visit_type_fn_mut(ast, params, varargs, ret, pos),
+            NodeType::TypeOptional(typ) => self.visit_type_optional_mut(ast, typ, pos),
+            NodeType::TypeUnion(types) => self.visit_type_union_mut(ast, types, pos),
+            NodeType::TypeTuple(types) => self.visit_type_tuple_mut(ast, types, pos),
+            NodeType::Error => self.visit_error_mut(ast, pos),
+        }
+        ast.replace(id, node);
+    }
+    fn visit_nodes_mut(&mut self, ast: &mut Ast, ids: &[NodeId]) {
+        for &id in ids { self.visit_node_mut(ast, id) }
+    }
+    fn visit_table_field_mut(&mut self, ast: &mut Ast, field: &mut TableField) {
+        match field {
+            TableField::Item(value) | TableField::Named(_, value) => self.visit_node_mut(ast, *value),
+            TableField::Keyed(key, value) => { self.visit_node_mut(ast, *key); self.visit_node_mut(ast, *value) }
+        }
+    }
+    fn visit_chunk_mut(&mut self, ast: &mut Ast, nodes: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, nodes) }
+    fn visit_do_block_mut(&mut self, ast: &mut Ast, nodes: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, nodes) }
+    fn visit_body_mut(&mut self, ast: &mut Ast, nodes: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, nodes) }
+    fn visit_id_mut(&mut self, ast: &mut Ast, id: &mut Symbol, pos: &mut Position) {}
+    fn visit_integer_mut(&mut self, ast: &mut Ast, value: &mut i64, pos: &mut Position) {}
+    fn visit_float_mut(&mut self, ast: &mut Ast, value: &mut f64, pos: &mut Position) {}
+    fn visit_boolean_mut(&mut self, ast: &mut Ast, value: &mut bool, pos: &mut Position) {}
+    fn visit_string_mut(&mut self, ast: &mut Ast, value: &mut Symbol, pos: &mut Position) {}
+    fn visit_nil_mut(&mut self, ast: &mut Ast, pos: &mut Position) {}
+    fn visit_varargs_mut(&mut self, ast: &mut Ast, pos: &mut Position) {}
+    fn visit_expr_mut(&mut self, ast: &mut Ast, node: &mut NodeId, pos: &mut Position) { self.visit_node_mut(ast, *node) }
+    fn visit_binary_mut(&mut self, ast: &mut Ast, left: &mut NodeId, op: &mut TokenType, right: &mut NodeId, pos: &mut Position) {
+        self.visit_node_mut(ast, *left);
+        self.visit_node_mut(ast, *right);
+    }
+    fn visit_unary_mut(&mut self, ast: &mut Ast, op: &mut TokenType, node: &mut NodeId, pos: &mut Position) { self.visit_node_mut(ast, *node) }
+    fn visit_field_mut(&mut self, ast: &mut Ast, left: &mut NodeId, right: &mut NodeId, expr: &mut bool, pos: &mut Position) {
+        self.visit_node_mut(ast, *left);
+        self.visit_node_mut(ast, *right);
+    }
+    fn visit_call_mut(&mut self, ast: &mut Ast, head: &mut NodeId, args: &mut Vec<NodeId>, pos: &mut Position) {
+        self.visit_node_mut(ast, *head);
+        self.visit_nodes_mut(ast, args);
+    }
+    fn visit_self_call_mut(&mut self, ast: &mut Ast, head: &mut NodeId, field: &mut Symbol, args: &mut Vec<NodeId>, pos: &mut Position) {
+        self.visit_node_mut(ast, *head);
+        self.visit_nodes_mut(ast, args);
+    }
+    fn visit_assign_mut(&mut self, ast: &mut Ast, var: &mut NodeId, expr: &mut NodeId, pos: &mut Position) {
+        self.visit_node_mut(ast, *var);
+        self.visit_node_mut(ast, *expr);
+    }
+    fn visit_assign_vars_mut(&mut self, ast: &mut Ast, vars: &mut Vec<NodeId>, exprs: &mut Vec<NodeId>, pos: &mut Position) {
+        self.visit_nodes_mut(ast, vars);
+        self.visit_nodes_mut(ast, exprs);
+    }
+    fn visit_local_assign_mut(&mut self, ast: &mut Ast, var: &mut NodeId, expr: &mut Option<NodeId>, pos: &mut Position) {
+        self.visit_node_mut(ast, *var);
+        if let Some(expr) = expr { self.visit_node_mut(ast, *expr) }
+    }
+    fn visit_local_assign_vars_mut(&mut self, ast: &mut Ast, vars: &mut Vec<NodeId>, exprs: &mut Vec<NodeId>, pos: &mut Position) {
+        self.visit_nodes_mut(ast, vars);
+        self.visit_nodes_mut(ast, exprs);
+    }
+    fn visit_return_mut(&mut self, ast: &mut Ast, exprs: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, exprs) }
+    fn visit_break_mut(&mut self, ast: &mut Ast, pos: &mut Position) {}
+    fn visit_goto_mut(&mut self, ast: &mut Ast, name: &mut Symbol, pos: &mut Position) {}
+    fn visit_label_mut(&mut self, ast: &mut Ast, name: &mut Symbol, pos: &mut Position) {}
+    fn visit_if_mut(&mut self, ast: &mut Ast, conds: &mut Vec<NodeId>, cases: &mut Vec<NodeId>, else_case: &mut Option<NodeId>, pos: &mut Position) {
+        self.visit_nodes_mut(ast, conds);
+        self.visit_nodes_mut(ast, cases);
+        if let Some(else_case) = else_case { self.visit_node_mut(ast, *else_case) }
+    }
+    fn visit_while_mut(&mut self, ast: &mut Ast, cond: &mut NodeId, body: &mut NodeId, pos: &mut Position) {
+        self.visit_node_mut(ast, *cond);
+        self.visit_node_mut(ast, *body);
+    }
+    fn visit_repeat_mut(&mut self, ast: &mut Ast, body: &mut NodeId, cond: &mut NodeId, pos: &mut Position) {
+        self.visit_node_mut(ast, *body);
+        self.visit_node_mut(ast, *cond);
+    }
+    fn visit_for_in_mut(&mut self, ast: &mut Ast, vars: &mut Vec<Symbol>, iter: &mut NodeId, body: &mut NodeId, pos: &mut Position) {
+        self.visit_node_mut(ast, *iter);
+        self.visit_node_mut(ast, *body);
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn visit_for_mut(&mut self, ast: &mut Ast, var: &mut Symbol, start: &mut NodeId, end: &mut NodeId, step: &mut Option<NodeId>, body: &mut NodeId, pos: &mut Position) {
+        self.visit_node_mut(ast, *start);
+        self.visit_node_mut(ast, *end);
+        if let Some(step) = step { self.visit_node_mut(ast, *step) }
+        self.visit_node_mut(ast, *body);
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn visit_function_mut(&mut self, ast: &mut Ast, path: &mut Vec<Symbol>, method: &mut Option<Symbol>, params: &mut Vec<NodeId>, varargs: &mut bool, ret: &mut Option<NodeId>, body: &mut NodeId, pos: &mut Position) {
+        self.visit_nodes_mut(ast, params);
+        if let Some(ret) = ret { self.visit_node_mut(ast, *ret) }
+        self.visit_node_mut(ast, *body);
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn visit_local_function_mut(&mut self, ast: &mut Ast, name: &mut Symbol, params: &mut Vec<NodeId>, varargs: &mut bool, ret: &mut Option<NodeId>, body: &mut NodeId, pos: &mut Position) {
+        self.visit_nodes_mut(ast, params);
+        if let Some(ret) = ret { self.visit_node_mut(ast, *ret) }
+        self.visit_node_mut(ast, *body);
+    }
+    fn visit_lambda_mut(&mut self, ast: &mut Ast, params: &mut Vec<NodeId>, varargs: &mut bool, ret: &mut Option<NodeId>, body: &mut NodeId, pos: &mut Position) {
+        self.visit_nodes_mut(ast, params);
+        if let Some(ret) = ret { self.visit_node_mut(ast, *ret) }
+        self.visit_node_mut(ast, *body);
+    }
+    fn visit_meta_mut(&mut self, ast: &mut Ast, name: &mut Symbol, generics: &mut Vec<Symbol>, funcs: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, funcs) }
+    fn visit_implement_mut(&mut self, ast: &mut Ast, name: &mut Symbol, generics: &mut Vec<Symbol>, args: &mut Vec<NodeId>, funcs: &mut Vec<NodeId>, pos: &mut Position) {
+        self.visit_nodes_mut(ast, args);
+        self.visit_nodes_mut(ast, funcs);
+    }
+    fn visit_table_mut(&mut self, ast: &mut Ast, fields: &mut Vec<TableField>, pos: &mut Position) { for field in fields { self.visit_table_field_mut(ast, field) } }
+    fn visit_var_mut(&mut self, ast: &mut Ast, name: &mut Symbol, typ: &mut Option<NodeId>, pos: &mut Position) { if let Some(typ) = typ { self.visit_node_mut(ast, *typ) } }
+    fn visit_type_name_mut(&mut self, ast: &mut Ast, name: &mut Symbol, args: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, args) }
+    fn visit_type_fn_mut(&mut self, ast: &mut Ast, params: &mut Vec<NodeId>, varargs: &mut Option<NodeId>, ret: &mut Option<NodeId>, pos: &mut Position) {
+        self.visit_nodes_mut(ast, params);
+        if let Some(varargs) = varargs { self.visit_node_mut(ast, *varargs) }
+        if let Some(ret) = ret { self.visit_node_mut(ast, *ret) }
+    }
+    fn visit_type_optional_mut(&mut self, ast: &mut Ast, typ: &mut NodeId, pos: &mut Position) { self.visit_node_mut(ast, *typ) }
+    fn visit_type_union_mut(&mut self, ast: &mut Ast, types: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, types) }
+    fn visit_type_tuple_mut(&mut self, ast: &mut Ast, types: &mut Vec<NodeId>, pos: &mut Position) { self.visit_nodes_mut(ast, types) }
+    fn visit_error_mut(&mut self, ast: &mut Ast, pos: &mut Position) {}
+}
+
+// rebuilds the tree, every method defaults to folding the children of its node into the same kind of node
+// passes rewrite a node by returning a different `Node` from its method, which takes the id of the old one
+pub trait Fold {
+    fn fold_node(&mut self, ast: &mut Ast, id: NodeId) -> NodeId {
+        let placeholder = Node::new(NodeType::Error, ast.pos(id).clone());
+        let Node { id:_, node, pos } = ast.replace(id, placeholder);
+        let node = match node {
+            NodeType::Chunk(nodes) => self.fold_chunk(ast, nodes, pos),
+            NodeType::DoBlock(nodes) => self.fold_do_block(ast, nodes, pos),
+            NodeType::Body(nodes) => self.fold_body(ast, nodes, pos),
+            NodeType::ID(id) => self.fold_id(ast, id, pos),
+            NodeType::Integer(value) => self.fold_integer(ast, value, pos),
+            NodeType::Float(value) => self.fold_float(ast, value, pos),
+            NodeType::Boolean(value) => self.fold_boolean(ast, value, pos),
+            NodeType::String(value) => self.fold_string(ast, value, pos),
+            NodeType::Nil => self.fold_nil(ast, pos),
+            NodeType::Varargs => self.fold_varargs(ast, pos),
+            NodeType::Expr(node) => self.fold_expr(ast, node, pos),
+            NodeType::Binary { left, op, right } => self.fold_binary(ast, left, op, right, pos),
+            NodeType::Unary { op, node } => self.fold_unary(ast, op, node, pos),
+            NodeType::Field { left, right, expr } => self.fold_field(ast, left, right, expr, pos),
+            NodeType::Call { head, args } => self.fold_call(ast, head, args, pos),
+            NodeType::SelfCall { head, field, args } => self.fold_self_call(ast, head, field, args, pos),
+            NodeType::Assign(var, expr) => self.fold_assign(ast, var, expr, pos),
+            NodeType::AssignVars(vars, exprs) => self.fold_assign_vars(ast, vars, exprs, pos),
+            NodeType::LocalAssign(var, expr) => self.fold_local_assign(ast, var, expr, pos),
+            NodeType::LocalAssignVars(vars, exprs) => self.fold_local_assign_vars(ast, vars, exprs, pos),
+            NodeType::Return(exprs) => self.fold_return(ast, exprs, pos),
+            NodeType::Break => self.fold_break(ast, pos),
+            NodeType::Goto(name) => self.fold_goto(ast, name, pos),
+            NodeType::Label(name) => self.fold_label(ast, name, pos),
+            NodeType::If { conds, cases, else_case } => self.fold_if(ast, conds, cases, else_case, pos),
+            NodeType::While { cond, body } => self.fold_while(ast, cond, body, pos),
+            NodeType::Repeat { body, cond } => self.fold_repeat(ast, body, cond, pos),
+            NodeType::ForIn { vars, iter, body } => self.fold_for_in(ast, vars, iter, body, pos),
+            NodeType::For { var, start, end, step, body } => self.fold_for(ast, var, start, end, step, body, pos),
+            NodeType::Function { path, method, params, varargs, ret, body } => self.fold_function(ast, path, method, params, varargs, ret, body, pos),
+            NodeType::LocalFunction { name, params, varargs, ret, body } => self.fold_local_function(ast, name, params, varargs, ret, body, pos),
+            NodeType::Lambda { params, varargs, ret, body } => self.fold_lambda(ast, params, varargs, ret, body, pos),
+            NodeType::Meta { name, generics, funcs } => self.fold_meta(ast, name, generics, funcs, pos),
+            NodeType::Implement { name, generics, args, funcs } => self.fold_implement(ast, name, generics, args, funcs, pos),
+            NodeType::Table(fields) => self.fold_table(ast, fields, pos),
+            NodeType::Var { name, typ } => self.fold_var(ast, name, typ, pos),
+            NodeType::TypeName { name, args } => self.fold_type_name(ast, name, args, pos),
+            NodeType::TypeFn { params, varargs, ret } => self.fold_type_fn(ast, params, varargs, ret, pos),
+            NodeType::TypeOptional(typ) => self.fold_type_optional(ast, typ, pos),
+            NodeType::TypeUnion(types) => self.fold_type_union(ast, types, pos),
+            NodeType::TypeTuple(types) => self.fold_type_tuple(ast, types, pos),
+            NodeType::Error => self.fold_error(ast, pos),
+        };
+        ast.replace(id, node);
+        id
+    }
+    fn fold_nodes(&mut self, ast: &mut Ast, ids: Vec<NodeId>) -> Vec<NodeId> {
+        ids.into_iter().map(|x| self.fold_node(ast, x)).collect()
+    }
+    fn fold_table_field(&mut self, ast: &mut Ast, field: TableField) -> TableField {
+        match field {
+            TableField::Item(value) => TableField::Item(self.fold_node(ast, value)),
+            TableField::Named(name, value) => TableField::Named(name, self.fold_node(ast, value)),
+            TableField::Keyed(key, value) => TableField::Keyed(self.fold_node(ast, key), self.fold_node(ast, value)),
+        }
+    }
+    fn fold_chunk(&mut self, ast: &mut Ast, nodes: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::Chunk(self.fold_nodes(ast, nodes)), pos)
+    }
+    fn fold_do_block(&mut self, ast: &mut Ast, nodes: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::DoBlock(self.fold_nodes(ast, nodes)), pos)
+    }
+    fn fold_body(&mut self, ast: &mut Ast, nodes: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::Body(self.fold_nodes(ast, nodes)), pos)
+    }
+    fn fold_id(&mut self, ast: &mut Ast, id: Symbol, pos: Position) -> Node {
+        Node::new(NodeType::ID(id), pos)
+    }
+    fn fold_integer(&mut self, ast: &mut Ast, value: i64, pos: Position) -> Node {
+        Node::new(NodeType::Integer(value), pos)
+    }
+    fn fold_float(&mut self, ast: &mut Ast, value: f64, pos: Position) -> Node {
+        Node::new(NodeType::Float(value), pos)
+    }
+    fn fold_boolean(&mut self, ast: &mut Ast, value: bool, pos: Position) -> Node {
+        Node::new(NodeType::Boolean(value), pos)
+    }
+    fn fold_string(&mut self, ast: &mut Ast, value: Symbol, pos: Position) -> Node {
+        Node::new(NodeType::String(value), pos)
+    }
+    fn fold_nil(&mut self, ast: &mut Ast, pos: Position) -> Node {
+        Node::new(NodeType::Nil, pos)
+    }
+    fn fold_varargs(&mut self, ast: &mut Ast, pos: Position) -> Node {
+        Node::new(NodeType::Varargs, pos)
+    }
+    fn fold_expr(&mut self, ast: &mut Ast, node: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::Expr(self.fold_node(ast, node)), pos)
+    }
+    fn fold_binary(&mut self, ast: &mut Ast, left: NodeId, op: TokenType, right: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::Binary { left: self.fold_node(ast, left), op, right: self.fold_node(ast, right) }, pos)
+    }
+    fn fold_unary(&mut self, ast: &mut Ast, op: TokenType, node: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::Unary { op, node: self.fold_node(ast, node) }, pos)
+    }
+    fn fold_field(&mut self, ast: &mut Ast, left: NodeId, right: NodeId, expr: bool, pos: Position) -> Node {
+        Node::new(NodeType::Field { left: self.fold_node(ast, left), right: self.fold_node(ast, right), expr }, pos)
+    }
+    fn fold_call(&mut self, ast: &mut Ast, head: NodeId, args: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::Call { head: self.fold_node(ast, head), args: self.fold_nodes(ast, args) }, pos)
+    }
+    fn fold_self_call(&mut self, ast: &mut Ast, head: NodeId, field: Symbol, args: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::SelfCall { head: self.fold_node(ast, head), field, args: self.fold_nodes(ast, args) }, pos)
+    }
+    fn fold_assign(&mut self, ast: &mut Ast, var: NodeId, expr: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::Assign(self.fold_node(ast, var), self.fold_node(ast, expr)), pos)
+    }
+    fn fold_assign_vars(&mut self, ast: &mut Ast, vars: Vec<NodeId>, exprs: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::AssignVars(self.fold_nodes(ast, vars), self.fold_nodes(ast, exprs)), pos)
+    }
+    fn fold_local_assign(&mut self, ast: &mut Ast, var: NodeId, expr: Option<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::LocalAssign(self.fold_node(ast, var), expr.map(|x| self.fold_node(ast, x))), pos)
+    }
+    fn fold_local_assign_vars(&mut self, ast: &mut Ast, vars: Vec<NodeId>, exprs: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::LocalAssignVars(self.fold_nodes(ast, vars), self.fold_nodes(ast, exprs)), pos)
+    }
+    fn fold_return(&mut self, ast: &mut Ast, exprs: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::Return(self.fold_nodes(ast, exprs)), pos)
+    }
+    fn fold_break(&mut self, ast: &mut Ast, pos: Position) -> Node {
+        Node::new(NodeType::Break, pos)
+    }
+    fn fold_goto(&mut self, ast: &mut Ast, name: Symbol, pos: Position) -> Node {
+        Node::new(NodeType::Goto(name), pos)
+    }
+    fn fold_label(&mut self, ast: &mut Ast, name: Symbol, pos: Position) -> Node {
+        Node::new(NodeType::Label(name), pos)
+    }
+    fn fold_if(&mut self, ast: &mut Ast, conds: Vec<NodeId>, cases: Vec<NodeId>, else_case: Option<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::If { conds: self.fold_nodes(ast, conds), cases: self.fold_nodes(ast, cases), else_case: else_case.map(|x| self.fold_node(ast, x)) }, pos)
+    }
+    fn fold_while(&mut self, ast: &mut Ast, cond: NodeId, body: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::While { cond: self.fold_node(ast, cond), body: self.fold_node(ast, body) }, pos)
+    }
+    fn fold_repeat(&mut self, ast: &mut Ast, body: NodeId, cond: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::Repeat { body: self.fold_node(ast, body), cond: self.fold_node(ast, cond) }, pos)
+    }
+    fn fold_for_in(&mut self, ast: &mut Ast, vars: Vec<Symbol>, iter: NodeId, body: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::ForIn { vars, iter: self.fold_node(ast, iter), body: self.fold_node(ast, body) }, pos)
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn fold_for(&mut self, ast: &mut Ast, var: Symbol, start: NodeId, end: NodeId, step: Option<NodeId>, body: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::For { var, start: self.fold_node(ast, start), end: self.fold_node(ast, end), step: step.map(|x| self.fold_node(ast, x)), body: self.fold_node(ast, body) }, pos)
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn fold_function(&mut self, ast: &mut Ast, path: Vec<Symbol>, method: Option<Symbol>, params: Vec<NodeId>, varargs: bool, ret: Option<NodeId>, body: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::Function { path, method, params: self.fold_nodes(ast, params), varargs, ret: ret.map(|x| self.fold_node(ast, x)), body: self.fold_node(ast, body) }, pos)
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn fold_local_function(&mut self, ast: &mut Ast, name: Symbol, params: Vec<NodeId>, varargs: bool, ret: Option<NodeId>, body: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::LocalFunction { name, params: self.fold_nodes(ast, params), varargs, ret: ret.map(|x| self.fold_node(ast, x)), body: self.fold_node(ast, body) }, pos)
+    }
+    fn fold_lambda(&mut self, ast: &mut Ast, params: Vec<NodeId>, varargs: bool, ret: Option<NodeId>, body: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::Lambda { params: self.fold_nodes(ast, params), varargs, ret: ret.map(|x| self.fold_node(ast, x)), body: self.fold_node(ast, body) }, pos)
+    }
+    fn fold_meta(&mut self, ast: &mut Ast, name: Symbol, generics: Vec<Symbol>, funcs: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::Meta { name, generics, funcs: self.fold_nodes(ast, funcs) }, pos)
+    }
+    fn fold_implement(&mut self, ast: &mut Ast, name: Symbol, generics: Vec<Symbol>, args: Vec<NodeId>, funcs: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::Implement { name, generics, args: self.fold_nodes(ast, args), funcs: self.fold_nodes(ast, funcs) }, pos)
+    }
+    fn fold_table(&mut self, ast: &mut Ast, fields: Vec<TableField>, pos: Position) -> Node {
+        Node::new(NodeType::Table(fields.into_iter().map(|x| self.fold_table_field(ast, x)).collect()), pos)
+    }
+    fn fold_var(&mut self, ast: &mut Ast, name: Symbol, typ: Option<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::Var { name, typ: typ.map(|x| self.fold_node(ast, x)) }, pos)
+    }
+    fn fold_type_name(&mut self, ast: &mut Ast, name: Symbol, args: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::TypeName { name, args: self.fold_nodes(ast, args) }, pos)
+    }
+    fn fold_type_fn(&mut self, ast: &mut Ast, params: Vec<NodeId>, varargs: Option<NodeId>, ret: Option<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::TypeFn { params: self.fold_nodes(ast, params), varargs: varargs.map(|x| self.fold_node(ast, x)), ret: ret.map(|x| self.fold_node(ast, x)) }, pos)
+    }
+    fn fold_type_optional(&mut self, ast: &mut Ast, typ: NodeId, pos: Position) -> Node {
+        Node::new(NodeType::TypeOptional(self.fold_node(ast, typ)), pos)
+    }
+    fn fold_type_union(&mut self, ast: &mut Ast, types: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::TypeUnion(self.fold_nodes(ast, types)), pos)
+    }
+    fn fold_type_tuple(&mut self, ast: &mut Ast, types: Vec<NodeId>, pos: Position) -> Node {
+        Node::new(NodeType::TypeTuple(self.fold_nodes(ast, types)), pos)
+    }
+    fn fold_error(&mut self, ast: &mut Ast, pos: Position) -> Node {
+        Node::new(NodeType::Error, pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanning::{lexer::Lexer, parser::parse};
+
+    fn ast(text: &str) -> Ast {
+        let (ast, errors) = parse(0, Lexer::new(0, text));
+        assert!(errors.is_empty(), "{errors:?}");
+        ast
+    }
+    fn code(ast: &Ast) -> String { ast.format(ast.root(), 0, false) }
+
+    struct CountIds(Vec<Symbol>);
+    impl Visitor for CountIds {
+        fn visit_id(&mut self, ast: &Ast, id: Symbol, pos: &Position) { self.0.push(id) }
+    }
+    #[test]
+    fn visitor_reaches_every_id() {
+        let ast = ast("local a = b + c(d, {e, [f] = g})\nfunction h(x) return x.y end");
+        let mut ids = CountIds(vec![]);
+        ids.visit_node(&ast, ast.root());
+        assert_eq!(ids.0.iter().map(|x| x.to_string()).collect::<Vec<_>>(), ["b", "c", "d", "e", "f", "g", "x", "y"]);
+    }
+
+    struct Rename;
+    impl VisitorMut for Rename {
+        fn visit_id_mut(&mut self, ast: &mut Ast, id: &mut Symbol, pos: &mut Position) {
+            *id = Symbol::intern(&format!("_{id}"));
+        }
+    }
+    #[test]
+    fn visitor_mut_changes_nodes_in_place() {
+        let mut ast = ast("print(a + b)");
+        let root = ast.root();
+        Rename.visit_node_mut(&mut ast, root);
+        assert_eq!(code(&ast), "_print(_a + _b)");
+    }
+
+    // adds integer constants
+    struct Constants;
+    impl Fold for Constants {
+        fn fold_binary(&mut self, ast: &mut Ast, left: NodeId, op: TokenType, right: NodeId, pos: Position) -> Node {
+            let (left, right) = (self.fold_node(ast, left), self.fold_node(ast, right));
+            match (ast.get(left).node(), &op, ast.get(right).node()) {
+                (NodeType::Integer(a), TokenType::Add, NodeType::Integer(b)) => Node::new(NodeType::Integer(a + b), pos),
+                _ => Node::new(NodeType::Binary { left, op, right }, pos)
+            }
+        }
+    }
+    #[test]
+    fn fold_rewrites_nodes() {
+        let mut ast = ast("local a = 1 + 2 + 3\nlocal b = 1 + x");
+        let root = ast.root();
+        let NodeType::Chunk(stats) = ast.get(root).node().clone() else { panic!() };
+        let NodeType::LocalAssign(_, Some(sum)) = *ast.get(stats[0]).node() else { panic!() };
+        assert_eq!(Constants.fold_node(&mut ast, root), root);
+        assert_eq!(ast.get(sum).node(), &NodeType::Integer(6));
+        assert_eq!(ast.get(sum).id(), sum);
+        assert_eq!(code(&ast), "local a = 6\nlocal b = 1 + x");
     }
 }
\ No newline at end of file
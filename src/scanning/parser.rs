@@ -1,127 +1,334 @@
-use crate::error::Error;
-use super::{nodes::{Node, NodeType}, tokens::{Token, TokenType}, position::Position};
+use std::collections::VecDeque;
+use crate::error::{Error, ErrorKind};
+use super::{nodes::{Ast, NodeId, NodeType, TableField}, tokens::{Token, TokenType, UNARY_PRIORITY}, position::{Position, FileId}, symbol::Symbol};
 
-pub type ParseResult = Result<Node, Error>;
-pub struct Parser {
-    path: String,
-    tokens: Vec<Token>,
-    idx: usize
+pub type ParseResult = Result<NodeId, Error>;
+// parameters, varargs, return type, body
+pub type FuncBody = (Vec<NodeId>, bool, Option<NodeId>, NodeId);
+// tokens the parser looks at before consuming them, the current one and the one after it
+pub const LOOKAHEAD: usize = 2;
+
+pub struct Parser<I: Iterator<Item = Result<Token, Error>>> {
+    file: FileId,
+    tokens: I,
+    buffer: VecDeque<Token>,
+    // how many tokens were consumed
+    idx: usize,
+    // the position of the last consumed token
+    last: Option<Position>,
+    ast: Ast,
+    // errors the parser recovered from
+    errors: Vec<Error>,
+    // the error that ended the token stream and how many errors were found before it
+    lex_error: Option<(Error, usize)>
 }
-impl Parser {
-    pub fn new(path: &String, tokens: Vec<Token>) -> Self {
-        Self { path: path.clone(), tokens, idx: 0 }
-    }
-    pub fn get(&self) -> Option<&TokenType> { Some(self.tokens.get(self.idx)?.token()) }
-    pub fn get_clone(&self) -> Option<TokenType> { Some(self.tokens.get(self.idx)?.token().clone()) }
-    pub fn pos(&self) -> Option<&Position> { Some(self.tokens.get(self.idx)?.pos()) }
-    pub fn pos_clone(&self) -> Option<Position> { Some(self.tokens.get(self.idx)?.pos().clone()) }
-    pub fn advance(&mut self) { self.idx += 1; }
-    pub fn advance_some(&mut self) -> Result<(), Error> {
+impl<I: Iterator<Item = Result<Token, Error>>> Parser<I> {
+    pub fn new(file: FileId, tokens: I) -> Self {
+        let mut parser = Self { file, tokens, buffer: VecDeque::new(), idx: 0, last: None, ast: Ast::default(), errors: vec![], lex_error: None };
+        parser.fill();
+        parser
+    }
+    // pulls tokens until the lookahead is full, a lexing error ends the stream like the end of the file does
+    pub fn fill(&mut self) {
+        while self.buffer.len() < LOOKAHEAD && self.lex_error.is_none() {
+            match self.tokens.next() {
+                Some(Ok(token)) => self.buffer.push_back(token),
+                Some(Err(err)) => self.lex_error = Some((err, self.errors.len())),
+                None => break
+            }
+        }
+    }
+    pub fn get(&self) -> Option<&TokenType> { Some(self.buffer.front()?.token()) }
+    pub fn get_clone(&self) -> Option<TokenType> { Some(self.buffer.front()?.token().clone()) }
+    pub fn peek(&self) -> Option<&TokenType> { Some(self.buffer.get(1)?.token()) }
+    pub fn pos(&self) -> Option<&Position> { Some(self.buffer.front()?.pos()) }
+    pub fn pos_clone(&self) -> Option<Position> { Some(self.buffer.front()?.pos().clone()) }
+    pub fn advance(&mut self) {
+        let Some(token) = self.buffer.pop_front() else { return };
+        self.last = Some(token.pos().clone());
         self.idx += 1;
-        if self.get() == None { return Err(Error::UnexpectedEOF) }
+        self.fill();
+    }
+    pub fn advance_some(&mut self) -> Result<(), Error> {
+        self.advance();
+        if self.get().is_none() { return Err(self.error(ErrorKind::UnexpectedEOF)) }
         Ok(())
     }
-    pub fn reverse(&mut self) { if self.idx > 0 { self.idx -= 1; } }
+    // errors point at the current token, or the last one at the end of the file
+    pub fn error(&self, kind: ErrorKind) -> Error {
+        let pos = self.pos().or(self.last.as_ref()).cloned();
+        Error::new(kind, pos.unwrap_or_else(|| Position::zero(self.file)))
+    }
+    pub fn error_at(&self, kind: ErrorKind, pos: &Position) -> Error {
+        Error::new(kind, pos.clone())
+    }
+    pub fn unexpected(&self, id: NodeId) -> Error {
+        let node = self.ast.get(id);
+        self.error_at(ErrorKind::UnexpectedNode(node.node().name().to_string()), node.pos())
+    }
+    pub fn errors(&self) -> &[Error] { &self.errors }
+    // records the error of a statement starting at the `start`th token and skips to the next token a statement can start or end at
+    // `opened` is the keyword of a statement closed by `end`
+    pub fn recover(&mut self, err: Error, start: usize, start_pos: Option<Position>, opened: Option<TokenType>) -> NodeId {
+        // an error at the end of the file is reported by every unfinished block
+        if self.errors.last() != Some(&err) { self.errors.push(err) }
+        let mut pos = start_pos.or(self.last.clone()).unwrap_or_else(|| Position::zero(self.file));
+        if self.idx == start && self.get().is_some() { self.advance() }
+        self.skip();
+        // the rest of the block is still read so its `end` isn't reported as well
+        if let Some(opened) = opened {
+            if matches!(opened, TokenType::While | TokenType::For) && self.get() == Some(&TokenType::Do) { self.advance() }
+            while !matches!(self.get(), None | Some(TokenType::End)) {
+                match self.get().unwrap() {
+                    TokenType::Semi => self.advance(),
+                    TokenType::Else | TokenType::Elseif if opened == TokenType::If => {
+                        self.advance();
+                        self.skip();
+                    }
+                    _ => { self.recoverable_stat(); }
+                }
+            }
+            self.advance();
+        }
+        if let Some(last) = &self.last { pos.extend(last) }
+        self.ast.add(NodeType::Error, pos)
+    }
+    // moves to the next token a statement can start or end at
+    pub fn skip(&mut self) {
+        while let Some(token) = self.get() {
+            if matches!(token, TokenType::Local | TokenType::Fn | TokenType::If | TokenType::While | TokenType::For |
+                TokenType::Do | TokenType::Repeat | TokenType::Return | TokenType::Break | TokenType::Goto | TokenType::Label |
+                TokenType::Meta | TokenType::Implement | TokenType::End | TokenType::Else | TokenType::Elseif | TokenType::Until) { break }
+            self.advance();
+        }
+    }
     
     pub fn expect_token(&self, token: TokenType) -> Result<(), Error> {
         if self.get() != Some(&token) {
-            Err(Error::ExpectedToken(token, self.get_clone()))
+            Err(self.error(ErrorKind::ExpectedToken(token, self.get_clone())))
         } else {
-            if self.get() == None { return Err(Error::UnexpectedEOF) }
+            if self.get().is_none() { return Err(self.error(ErrorKind::UnexpectedEOF)) }
             Ok(())
         }
     }
-
-    pub fn parse(&mut self) -> ParseResult {
-        let Some(mut pos) = self.pos_clone() else {
-            return Ok(Node::new(NodeType::Chunk(vec![]), Position::zero()))
+    // `>>` closes two generic argument lists, the second `>` is left for the outer one
+    pub fn close_generics(&mut self) -> Result<Position, Error> {
+        if self.get() == Some(&TokenType::Shr) {
+            let span = self.pos().unwrap().span().clone();
+            self.buffer[0] = Token::new(TokenType::GT, Position::new(self.file, span.start + 1..span.end));
+            return Ok(Position::new(self.file, span.start..span.start + 1))
+        }
+        self.expect_token(TokenType::GT)?;
+        let pos = self.pos_clone().unwrap();
+        self.advance();
+        Ok(pos)
+    }
+    pub fn id(&mut self) -> Result<Symbol, Error> {
+        if self.get().is_none() { return Err(self.error(ErrorKind::UnexpectedEOF)) }
+        let Some(TokenType::ID(id)) = self.get_clone() else {
+            return Err(self.error(ErrorKind::ExpectedToken(TokenType::ID(Symbol::intern("")), self.get_clone())))
         };
+        self.advance();
+        Ok(id)
+    }
+
+    pub fn parse(&mut self) -> NodeId {
+        let mut pos = self.pos_clone().unwrap_or_else(|| Position::zero(self.file));
         let mut nodes = vec![];
-        while self.get().is_some() { nodes.push(self.stat()?); }
-        Ok(Node::new(NodeType::Chunk(nodes), pos))
+        while let Some(token) = self.get() {
+            // `;` is an empty statement
+            if token == &TokenType::Semi { self.advance(); continue }
+            nodes.push(self.recoverable_stat());
+        }
+        if let Some(last) = &self.last { pos.extend(last) }
+        let root = self.ast.add(NodeType::Chunk(nodes), pos);
+        self.ast.set_root(root);
+        root
     }
-    pub fn body(&mut self, tokens: Vec<TokenType>) -> Result<Vec<Node>, Error> {
+    pub fn body(&mut self, tokens: Vec<TokenType>) -> Result<Vec<NodeId>, Error> {
         let mut nodes = vec![];
         while let Some(token) = self.get() {
-            if tokens.contains(&token) { break }
-            nodes.push(self.stat()?);
+            if tokens.contains(token) { break }
+            if token == &TokenType::Semi { self.advance(); continue }
+            nodes.push(self.recoverable_stat());
         }
-        if self.get() == None { return Err(Error::UnexpectedEOF) }
+        if self.get().is_none() { return Err(self.error(ErrorKind::UnexpectedEOF)) }
         Ok(nodes)
     }
+    // a statement, or an error node in its place
+    pub fn recoverable_stat(&mut self) -> NodeId {
+        let (start, start_pos) = (self.idx, self.pos_clone());
+        let opened = match self.get() {
+            Some(TokenType::Local) if self.peek() == Some(&TokenType::Fn) => Some(TokenType::Fn),
+            Some(token @ (TokenType::Fn | TokenType::If | TokenType::While | TokenType::For | TokenType::Meta | TokenType::Implement)) => Some(token.clone()),
+            _ => None
+        };
+        self.stat().unwrap_or_else(|err| self.recover(err, start, start_pos, opened))
+    }
+    pub fn block_end(&self) -> bool {
+        matches!(self.get(), None | Some(TokenType::End | TokenType::Else | TokenType::Elseif | TokenType::Until))
+    }
     pub fn stat(&mut self) -> ParseResult {
         let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        if self.get().is_none() { return Err(Error::UnexpectedEOF) }
+        if self.get().is_none() { return Err(self.error(ErrorKind::UnexpectedEOF)) }
         match self.get().unwrap() {
+            TokenType::Fn => {
+                self.advance_some()?;
+                let mut path = vec![self.id()?];
+                while self.get() == Some(&TokenType::Field) {
+                    self.advance_some()?;
+                    path.push(self.id()?);
+                }
+                let mut method = None;
+                if self.get() == Some(&TokenType::Rep) {
+                    self.advance_some()?;
+                    method = Some(self.id()?);
+                }
+                let (params, varargs, ret, body) = self.func_body(&mut pos)?;
+                Ok(self.ast.add(NodeType::Function { path, method, params, varargs, ret, body }, pos))
+            }
+            TokenType::Meta => {
+                self.advance_some()?;
+                let generics = self.generics()?;
+                let name = self.id()?;
+                let funcs = self.field_functions(&mut pos)?;
+                Ok(self.ast.add(NodeType::Meta { name, generics, funcs }, pos))
+            }
+            TokenType::Implement => {
+                self.advance_some()?;
+                let generics = self.generics()?;
+                let name = self.id()?;
+                let mut args = vec![];
+                if self.get() == Some(&TokenType::LT) {
+                    self.advance_some()?;
+                    args.push(self.typ()?);
+                    while self.get() == Some(&TokenType::Sep) {
+                        self.advance_some()?;
+                        args.push(self.typ()?);
+                    }
+                    self.close_generics()?;
+                }
+                let funcs = self.field_functions(&mut pos)?;
+                Ok(self.ast.add(NodeType::Implement { name, generics, args, funcs }, pos))
+            }
             TokenType::Local => {
                 self.advance_some()?;
-                let var = self.expr()?;
-                pos.extend(var.pos());
+                if self.get() == Some(&TokenType::Fn) {
+                    self.advance_some()?;
+                    let name = self.id()?;
+                    let (params, varargs, ret, body) = self.func_body(&mut pos)?;
+                    return Ok(self.ast.add(NodeType::LocalFunction { name, params, varargs, ret, body }, pos))
+                }
+                let var = self.param()?;
+                pos.extend(self.ast.pos(var));
                 if self.get() == Some(&TokenType::Sep) {
                     let mut vars = vec![var];
                     while self.get() == Some(&TokenType::Sep) {
-                        self.advance();
-                        vars.push(self.expr()?);
+                        self.advance_some()?;
+                        vars.push(self.param()?);
+                        pos.extend(self.ast.pos(*vars.last().unwrap()));
                     }
-                    self.expect_token(TokenType::Assign)?;
-                    self.advance_some()?;
-                    let mut exprs = vec![self.expr()?];
-                    pos.extend(exprs.last().unwrap().pos());
-                    while self.get() == Some(&TokenType::Sep) {
+                    let mut exprs = vec![];
+                    if self.get() == Some(&TokenType::Assign) {
                         self.advance_some()?;
                         exprs.push(self.expr()?);
-                        pos.extend(exprs.last().unwrap().pos());
+                        pos.extend(self.ast.pos(*exprs.last().unwrap()));
+                        while self.get() == Some(&TokenType::Sep) {
+                            self.advance_some()?;
+                            exprs.push(self.expr()?);
+                            pos.extend(self.ast.pos(*exprs.last().unwrap()));
+                        }
                     }
-                    return Ok(Node::new(NodeType::LocalAssignVars(vars, exprs), pos))
+                    return Ok(self.ast.add(NodeType::LocalAssignVars(vars, exprs), pos))
                 }
                 let mut expr = None;
                 if self.get() == Some(&TokenType::Assign) {
                     self.advance_some()?;
-                    let expr_ = Box::new(self.expr()?);
-                    expr = Some(expr_.clone());
-                    pos.extend(expr_.pos());
+                    let expr_ = self.expr()?;
+                    expr = Some(expr_);
+                    pos.extend(self.ast.pos(expr_));
                 }
-                Ok(Node::new(NodeType::LocalAssign(Box::new(var), expr), pos))
+                Ok(self.ast.add(NodeType::LocalAssign(var, expr), pos))
             }
             TokenType::Return => {
-                self.advance_some()?;
-                let expr = Box::new(self.expr()?);
-                pos.extend(expr.pos());
-                Ok(Node::new(NodeType::Return(expr), pos))
+                self.advance();
+                let mut exprs = vec![];
+                if !self.block_end() && self.get() != Some(&TokenType::Semi) {
+                    exprs.push(self.expr()?);
+                    while self.get() == Some(&TokenType::Sep) {
+                        self.advance_some()?;
+                        exprs.push(self.expr()?);
+                    }
+                    pos.extend(self.ast.pos(*exprs.last().unwrap()));
+                }
+                if self.get() == Some(&TokenType::Semi) {
+                    pos.extend(self.pos().unwrap());
+                    self.advance();
+                }
+                // nothing can follow a return in its block
+                if !self.block_end() { return Err(self.error(ErrorKind::UnexpectedToken(self.get_clone().unwrap()))) }
+                Ok(self.ast.add(NodeType::Return(exprs), pos))
             }
             TokenType::Break => {
                 self.advance();
-                Ok(Node::new(NodeType::Break, pos))
+                Ok(self.ast.add(NodeType::Break, pos))
+            }
+            TokenType::Goto => {
+                self.advance_some()?;
+                pos.extend(self.pos().unwrap());
+                let name = self.id()?;
+                Ok(self.ast.add(NodeType::Goto(name), pos))
+            }
+            TokenType::Label => {
+                self.advance_some()?;
+                let name = self.id()?;
+                self.expect_token(TokenType::Label)?;
+                pos.extend(self.pos().unwrap());
+                self.advance();
+                Ok(self.ast.add(NodeType::Label(name), pos))
+            }
+            TokenType::Repeat => {
+                self.advance_some()?;
+                let mut body_pos = self.pos_clone().unwrap();
+                let body = self.body(vec![TokenType::Until])?;
+                body_pos.extend(self.pos().unwrap());
+                self.advance_some()?;
+                let cond = self.expr()?;
+                pos.extend(self.ast.pos(cond));
+                let body = self.ast.add(NodeType::Body(body), body_pos);
+                Ok(self.ast.add(NodeType::Repeat { body, cond }, pos))
             }
             TokenType::Do => {
                 self.advance_some()?;
                 let body = self.body(vec![TokenType::End])?;
                 pos.extend(self.pos().unwrap());
                 self.advance();
-                Ok(Node::new(NodeType::DoBlock(body), pos))
+                Ok(self.ast.add(NodeType::DoBlock(body), pos))
             }
             TokenType::While => {
                 self.advance_some()?;
-                let cond = Box::new(self.expr()?);
+                let cond = self.expr()?;
                 self.expect_token(TokenType::Do)?; self.advance_some()?;
-                let case_pos = self.pos_clone().unwrap();
+                let mut body_pos = self.pos_clone().unwrap();
                 let body = self.body(vec![TokenType::End])?;
-                pos.extend(self.pos().unwrap());
-                let body = Box::new(Node::new(NodeType::Body(body), pos.clone()));
+                body_pos.extend(self.pos().unwrap());
+                pos.extend(&body_pos);
+                let body = self.ast.add(NodeType::Body(body), body_pos);
                 self.advance();
-                Ok(Node::new(NodeType::While { cond, body }, pos))
+                Ok(self.ast.add(NodeType::While { cond, body }, pos))
             }
             TokenType::If => {
                 self.advance_some()?;
                 let (mut conds, mut cases) = (vec![], vec![]);
                 conds.push(self.expr()?);
                 self.expect_token(TokenType::Then)?; self.advance_some()?;
-                let case_pos = self.pos_clone().unwrap();
+                let mut case_pos = self.pos_clone().unwrap();
                 let case = self.body(vec![TokenType::End, TokenType::Elseif, TokenType::Else])?;
-                pos.extend(self.pos().unwrap());
-                cases.push(Node::new(NodeType::Body(case), case_pos));
+                case_pos.extend(self.pos().unwrap());
+                pos.extend(&case_pos);
+                cases.push(self.ast.add(NodeType::Body(case), case_pos));
                 while self.get() == Some(&TokenType::Elseif) {
                     self.advance_some()?;
                     conds.push(self.expr()?);
@@ -130,7 +337,7 @@ impl Parser {
                     let case = self.body(vec![TokenType::End, TokenType::Elseif, TokenType::Else])?;
                     case_pos.extend(self.pos().unwrap());
                     pos.extend(&case_pos);
-                    cases.push(Node::new(NodeType::Body(case), case_pos));
+                    cases.push(self.ast.add(NodeType::Body(case), case_pos));
                 }
                 let mut else_case = None;
                 if self.get() == Some(&TokenType::Else) {
@@ -140,77 +347,71 @@ impl Parser {
                     else_pos.extend(self.pos().unwrap());
                     pos.extend(&else_pos);
                     self.advance();
-                    else_case = Some(Box::new(Node::new(NodeType::Body(body), else_pos)))
+                    else_case = Some(self.ast.add(NodeType::Body(body), else_pos))
                 } else {
                     self.advance();
                 }
-                Ok(Node::new(NodeType::If { conds, cases, else_case }, pos))
+                Ok(self.ast.add(NodeType::If { conds, cases, else_case }, pos))
             }
             TokenType::For => {
                 self.advance_some()?;
-                let TokenType::ID(var) = self.get_clone().unwrap() else {
-                    return Err(Error::ExpectedToken(TokenType::ID("".to_string()), self.get_clone()))
-                };
-                self.advance_some()?;
+                let var = self.id()?;
                 if self.get() == Some(&TokenType::Sep) {
                     let mut vars = vec![var];
                     while self.get() == Some(&TokenType::Sep) {
-                        self.advance_some();
-                        let TokenType::ID(var) = self.get_clone().unwrap() else {
-                            return Err(Error::ExpectedToken(TokenType::ID("".to_string()), self.get_clone()))
-                        };
-                        self.advance_some();
-                        vars.push(var);
+                        self.advance_some()?;
+                        vars.push(self.id()?);
                     }
-                    self.expect_token(TokenType::In)?; self.advance_some();
-                    let iter = Box::new(self.expr()?);
-                    self.expect_token(TokenType::Do)?; self.advance_some();
+                    self.expect_token(TokenType::In)?; self.advance_some()?;
+                    let iter = self.expr()?;
+                    self.expect_token(TokenType::Do)?; self.advance_some()?;
                     let mut body_pos = self.pos_clone().unwrap();
                     let body = self.body(vec![TokenType::End])?;
                     body_pos.extend(self.pos().unwrap());
                     pos.extend(&body_pos);
                     self.advance();
-                    let body = Box::new(Node::new(NodeType::Body(body), body_pos));
-                    return Ok(Node::new(NodeType::ForIn { vars, iter, body }, pos))
+                    let body = self.ast.add(NodeType::Body(body), body_pos);
+                    return Ok(self.ast.add(NodeType::ForIn { vars, iter, body }, pos))
                 }
                 if self.get() == Some(&TokenType::In) {
-                    self.advance_some();
-                    let iter = Box::new(self.expr()?);
-                    self.expect_token(TokenType::Do)?; self.advance_some();
+                    self.advance_some()?;
+                    let iter = self.expr()?;
+                    self.expect_token(TokenType::Do)?; self.advance_some()?;
                     let mut body_pos = self.pos_clone().unwrap();
                     let body = self.body(vec![TokenType::End])?;
                     body_pos.extend(self.pos().unwrap());
                     pos.extend(&body_pos);
                     self.advance();
-                    let body = Box::new(Node::new(NodeType::Body(body), body_pos));
-                    return Ok(Node::new(NodeType::ForIn { vars: vec![var], iter, body }, pos))
+                    let body = self.ast.add(NodeType::Body(body), body_pos);
+                    return Ok(self.ast.add(NodeType::ForIn { vars: vec![var], iter, body }, pos))
                 }
-                self.expect_token(TokenType::Assign)?; self.advance_some();
-                let start = Box::new(self.expr()?);
-                self.expect_token(TokenType::Sep)?; self.advance_some();
-                let end = Box::new(self.expr()?);
+                self.expect_token(TokenType::Assign)?; self.advance_some()?;
+                let start = self.expr()?;
+                self.expect_token(TokenType::Sep)?; self.advance_some()?;
+                let end = self.expr()?;
                 let mut step = None;
                 if self.get() == Some(&TokenType::Sep) {
-                    self.advance_some();
-                    step = Some(Box::new(self.expr()?));
+                    self.advance_some()?;
+                    step = Some(self.expr()?);
                 }
-                self.expect_token(TokenType::Do)?; self.advance_some();
+                self.expect_token(TokenType::Do)?; self.advance_some()?;
                 let mut body_pos = self.pos_clone().unwrap();
                 let body = self.body(vec![TokenType::End])?;
                 body_pos.extend(self.pos().unwrap());
                 pos.extend(&body_pos);
                 self.advance();
-                let body = Box::new(Node::new(NodeType::Body(body), body_pos));
-                Ok(Node::new(NodeType::For { var, start, end, step, body }, pos))
+                let body = self.ast.add(NodeType::Body(body), body_pos);
+                Ok(self.ast.add(NodeType::For { var, start, end, step, body }, pos))
             }
             _ => {
                 let node = self.expr()?;
-                match node.node() {
+                match self.ast.get(node).node() {
                     // assigment
                     NodeType::ID(_) | NodeType::Field { left:_, right:_, expr:_ } if self.get() == Some(&TokenType::Assign) => {
                         self.advance_some()?;
-                        let expr = Box::new(self.expr()?);
-                        Ok(Node::new(NodeType::Assign(Box::new(node), expr), pos))
+                        let expr = self.expr()?;
+                        pos.extend(self.ast.pos(expr));
+                        Ok(self.ast.add(NodeType::Assign(node, expr), pos))
                     }
                     // multi assignment
                     NodeType::ID(_) | NodeType::Field { left:_, right:_, expr:_ } if self.get() == Some(&TokenType::Sep) => {
@@ -225,265 +426,438 @@ impl Parser {
                             self.advance_some()?;
                             exprs.push(self.expr()?);
                         }
-                        Ok(Node::new(NodeType::AssignVars(vars, exprs), pos))
+                        pos.extend(self.ast.pos(*exprs.last().unwrap()));
+                        Ok(self.ast.add(NodeType::AssignVars(vars, exprs), pos))
                     }
                     // let call pass through
-                    NodeType::Call { head:_, args:_ } => Ok(node),
-                    _ => Err(Error::UnexpectedNode(node.node().clone()))
+                    NodeType::Call { head:_, args:_ } | NodeType::SelfCall { head:_, field:_, args:_ } => Ok(node),
+                    _ => Err(self.unexpected(node))
                 }
             }
         }
     }
     
-    pub fn expr(&mut self) -> ParseResult {
-        self.or()
+    // functions with plain names up to `end`, they become fields of a table
+    pub fn field_functions(&mut self, pos: &mut Position) -> Result<Vec<NodeId>, Error> {
+        let mut funcs = vec![];
+        while self.get() != Some(&TokenType::End) {
+            self.expect_token(TokenType::Fn)?;
+            let func = self.stat()?;
+            if let NodeType::Function { path, method: None, params:_, varargs:_, ret:_, body:_ } = self.ast.get(func).node() {
+                if path.len() == 1 {
+                    funcs.push(func);
+                    continue
+                }
+            }
+            return Err(self.unexpected(func))
+        }
+        pos.extend(self.pos().unwrap());
+        self.advance();
+        Ok(funcs)
     }
-    pub fn or(&mut self) -> ParseResult {
-        let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
-        };
-        let mut left = self.and()?;
-        while let Some(token) = self.get() {
-            if ![TokenType::Or].contains(token) { break }
-            let op = self.get_clone().unwrap();
-            self.advance();
-            let right = Box::new(self.and()?);
-            pos.extend(right.pos());
-            left = Node::new(NodeType::Binary { left: Box::new(left.clone()), op, right }, pos.clone())
+    pub fn generics(&mut self) -> Result<Vec<Symbol>, Error> {
+        let mut generics = vec![];
+        if self.get() == Some(&TokenType::LT) {
+            self.advance_some()?;
+            generics.push(self.id()?);
+            while self.get() == Some(&TokenType::Sep) {
+                self.advance_some()?;
+                generics.push(self.id()?);
+            }
+            self.expect_token(TokenType::GT)?; self.advance_some()?;
         }
-        Ok(left)
+        Ok(generics)
     }
-    pub fn and(&mut self) -> ParseResult {
-        let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
-        };
-        let mut left = self.comp()?;
-        while let Some(token) = self.get() {
-            if ![TokenType::And].contains(token) { break }
-            let op = self.get_clone().unwrap();
-            self.advance();
-            let right = Box::new(self.comp()?);
-            pos.extend(right.pos());
-            left = Node::new(NodeType::Binary { left: Box::new(left.clone()), op, right }, pos.clone())
+    pub fn func_body(&mut self, pos: &mut Position) -> Result<FuncBody, Error> {
+        self.expect_token(TokenType::EvalIn)?; self.advance_some()?;
+        let (mut params, mut varargs) = (vec![], false);
+        while self.get() != Some(&TokenType::EvalOut) {
+            if self.get() == Some(&TokenType::Args) {
+                self.advance_some()?;
+                varargs = true;
+                break
+            }
+            params.push(self.param()?);
+            if self.get() != Some(&TokenType::Sep) { break }
+            self.advance_some()?;
         }
-        Ok(left)
+        self.expect_token(TokenType::EvalOut)?; self.advance_some()?;
+        let mut ret = None;
+        if self.get() == Some(&TokenType::Arrow) {
+            self.advance_some()?;
+            ret = Some(self.returns()?);
+        }
+        let Some(mut body_pos) = self.pos_clone() else {
+            return Err(self.error(ErrorKind::UnexpectedEOF))
+        };
+        let body = self.body(vec![TokenType::End])?;
+        body_pos.extend(self.pos().unwrap());
+        pos.extend(&body_pos);
+        self.advance();
+        Ok((params, varargs, ret, self.ast.add(NodeType::Body(body), body_pos)))
     }
-    pub fn comp(&mut self) -> ParseResult {
+    // `-> A` or `-> (A, B)`, a single parenthesized type is parsed as a normal type
+    pub fn returns(&mut self) -> ParseResult {
         let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        let mut left = self.concat()?;
-        while let Some(token) = self.get() {
-            if ![TokenType::EQ, TokenType::NE, TokenType::LT, TokenType::GT, TokenType::LE, TokenType::GE].contains(token) { break }
-            let op = self.get_clone().unwrap();
-            self.advance();
-            let right = Box::new(self.concat()?);
-            pos.extend(right.pos());
-            left = Node::new(NodeType::Binary { left: Box::new(left.clone()), op, right }, pos.clone())
+        if self.get() != Some(&TokenType::EvalIn) { return self.typ() }
+        self.advance_some()?;
+        let (mut types, mut tuple) = (vec![], false);
+        while self.get() != Some(&TokenType::EvalOut) {
+            types.push(self.typ()?);
+            if self.get() != Some(&TokenType::Sep) { break }
+            self.advance_some()?;
+            tuple = true;
         }
-        Ok(left)
+        self.expect_token(TokenType::EvalOut)?;
+        pos.extend(self.pos().unwrap());
+        self.advance();
+        if let ([typ], false) = (types.as_slice(), tuple) {
+            // it can still be followed by `?` and `|` like in any other type
+            let typ = self.optional_suffix(*typ, pos.clone());
+            return self.union_rest(typ, pos)
+        }
+        Ok(self.ast.add(NodeType::TypeTuple(types), pos))
     }
-    pub fn concat(&mut self) -> ParseResult {
+    pub fn param(&mut self) -> ParseResult {
         let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        let mut left = self.arith()?;
-        while let Some(token) = self.get() {
-            if ![TokenType::Concat].contains(token) { break }
-            let op = self.get_clone().unwrap();
-            self.advance();
-            let right = Box::new(self.arith()?);
-            pos.extend(right.pos());
-            left = Node::new(NodeType::Binary { left: Box::new(left.clone()), op, right }, pos.clone())
+        let name = self.id()?;
+        let mut typ = None;
+        if self.get() == Some(&TokenType::Rep) {
+            self.advance_some()?;
+            let typ_ = self.typ()?;
+            pos.extend(self.ast.pos(typ_));
+            typ = Some(typ_);
         }
-        Ok(left)
+        Ok(self.ast.add(NodeType::Var { name, typ }, pos))
     }
-    pub fn arith(&mut self) -> ParseResult {
-        let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+    pub fn typ(&mut self) -> ParseResult {
+        let Some(pos) = self.pos_clone() else {
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        let mut left = self.term()?;
-        while let Some(token) = self.get() {
-            if ![TokenType::Add, TokenType::Sub].contains(token) { break }
-            let op = self.get_clone().unwrap();
-            self.advance();
-            let right = Box::new(self.term()?);
-            pos.extend(right.pos());
-            left = Node::new(NodeType::Binary { left: Box::new(left.clone()), op, right }, pos.clone())
+        let typ = self.optional_type()?;
+        self.union_rest(typ, pos)
+    }
+    // the other members of a union starting with `first`
+    pub fn union_rest(&mut self, first: NodeId, mut pos: Position) -> ParseResult {
+        let mut types = vec![first];
+        while self.get() == Some(&TokenType::BOr) {
+            self.advance_some()?;
+            types.push(self.optional_type()?);
+            pos.extend(self.ast.pos(*types.last().unwrap()));
         }
-        Ok(left)
+        if types.len() == 1 { return Ok(types.pop().unwrap()) }
+        Ok(self.ast.add(NodeType::TypeUnion(types), pos))
     }
-    pub fn term(&mut self) -> ParseResult {
-        let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+    pub fn optional_type(&mut self) -> ParseResult {
+        let Some(pos) = self.pos_clone() else {
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        let mut left = self.factor()?;
-        while let Some(token) = self.get() {
-            if ![TokenType::Mul, TokenType::Div, TokenType::Mod].contains(token) { break }
-            let op = self.get_clone().unwrap();
+        let typ = self.type_atom()?;
+        Ok(self.optional_suffix(typ, pos))
+    }
+    pub fn optional_suffix(&mut self, mut typ: NodeId, mut pos: Position) -> NodeId {
+        while self.get() == Some(&TokenType::Opt) {
+            pos.extend(self.pos().unwrap());
             self.advance();
-            let right = Box::new(self.factor()?);
-            pos.extend(right.pos());
-            left = Node::new(NodeType::Binary { left: Box::new(left.clone()), op, right }, pos.clone())
+            typ = self.ast.add(NodeType::TypeOptional(typ), pos.clone());
         }
-        Ok(left)
+        typ
     }
-    pub fn factor(&mut self) -> ParseResult {
+    pub fn type_atom(&mut self) -> ParseResult {
         let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        match self.get().unwrap() {
-            TokenType::Not | TokenType::Sub => {
-                let op = self.get_clone().unwrap();
+        let name = match self.get_clone() {
+            Some(TokenType::Nil) => { self.advance(); Symbol::intern("nil") }
+            Some(TokenType::EvalIn) => {
+                self.advance_some()?;
+                let typ = self.typ()?;
+                self.expect_token(TokenType::EvalOut)?;
+                self.advance();
+                return Ok(typ)
+            }
+            Some(TokenType::Fn) => {
+                self.advance();
+                if self.get() != Some(&TokenType::EvalIn) {
+                    return Ok(self.ast.add(NodeType::TypeName { name: Symbol::intern("function"), args: vec![] }, pos))
+                }
+                self.advance_some()?;
+                let (mut params, mut varargs) = (vec![], None);
+                while self.get() != Some(&TokenType::EvalOut) {
+                    if self.get() == Some(&TokenType::Args) {
+                        let args_pos = self.pos_clone().unwrap();
+                        self.advance_some()?;
+                        // `...` alone accepts any values
+                        varargs = Some(if self.get() == Some(&TokenType::EvalOut) {
+                            self.ast.add(NodeType::TypeName { name: Symbol::intern("any"), args: vec![] }, args_pos)
+                        } else { self.typ()? });
+                        break
+                    }
+                    params.push(self.typ()?);
+                    if self.get() != Some(&TokenType::Sep) { break }
+                    self.advance_some()?;
+                }
+                self.expect_token(TokenType::EvalOut)?;
+                pos.extend(self.pos().unwrap());
                 self.advance();
-                let node = Box::new(self.factor()?);
-                pos.extend(node.pos());
-                Ok(Node::new(NodeType::Unary { op, node }, pos))
+                let mut ret = None;
+                if self.get() == Some(&TokenType::Arrow) {
+                    self.advance_some()?;
+                    let typ = self.returns()?;
+                    pos.extend(self.ast.pos(typ));
+                    ret = Some(typ);
+                }
+                return Ok(self.ast.add(NodeType::TypeFn { params, varargs, ret }, pos))
+            }
+            _ => self.id()?
+        };
+        let mut args = vec![];
+        if self.get() == Some(&TokenType::LT) {
+            self.advance_some()?;
+            args.push(self.typ()?);
+            while self.get() == Some(&TokenType::Sep) {
+                self.advance_some()?;
+                args.push(self.typ()?);
             }
-            _ => self.power()
+            pos.extend(&self.close_generics()?);
         }
+        Ok(self.ast.add(NodeType::TypeName { name, args }, pos))
     }
-    pub fn power(&mut self) -> ParseResult {
+
+    pub fn expr(&mut self) -> ParseResult {
+        self.binary(0)
+    }
+    // an expression whose operators all have a left priority above the limit, like `subexpr` in the reference implementation
+    pub fn binary(&mut self, limit: u8) -> ParseResult {
         let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        let mut left = self.len()?;
+        let mut left = if self.get().unwrap().is_unary() {
+            let op = self.get_clone().unwrap();
+            self.advance();
+            let node = self.binary(UNARY_PRIORITY)?;
+            pos.extend(self.ast.pos(node));
+            self.ast.add(NodeType::Unary { op, node }, pos.clone())
+        } else { self.call()? };
         while let Some(token) = self.get() {
-            if ![TokenType::Pow].contains(token) { break }
+            let (left_priority, right_priority) = token.binary_priority();
+            if left_priority <= limit { break }
             let op = self.get_clone().unwrap();
             self.advance();
-            let right = Box::new(self.len()?);
-            pos.extend(right.pos());
-            left = Node::new(NodeType::Binary { left: Box::new(left.clone()), op, right }, pos.clone())
+            let right = self.binary(right_priority)?;
+            pos.extend(self.ast.pos(right));
+            left = self.ast.add(NodeType::Binary { left, op, right }, pos.clone())
         }
         Ok(left)
     }
-    pub fn len(&mut self) -> ParseResult {
-        let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
-        };
-        match self.get().unwrap() {
-            TokenType::Len => {
-                let op = self.get_clone().unwrap();
-                self.advance();
-                let node = Box::new(self.call()?);
-                pos.extend(node.pos());
-                Ok(Node::new(NodeType::Unary { op, node }, pos))
-            }
-            _ => self.call()
-        }
-    }
-    
+    // an atom followed by any number of fields, indexes and calls, like `suffixedexp` in the reference implementation
     pub fn call(&mut self) -> ParseResult {
         let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
+            return Err(self.error(ErrorKind::UnexpectedEOF))
         };
-        let mut left = self.field()?;
+        let mut left = self.atom()?;
         while let Some(token) = self.get() {
             match token {
-                // normal call
-                TokenType::EvalIn => {
+                TokenType::Field => {
                     self.advance();
-                    if self.get() == Some(&TokenType::EvalOut) {
-                        pos.extend(self.pos().unwrap());
-                        self.advance();
-                        left = Node::new(NodeType::Call { head: Box::new(left.clone()), args: vec![] }, pos.clone());
-                        continue
-                    }
-                    let mut args = vec![self.expr()?];
-                    while self.get() == Some(&TokenType::Sep) {
-                        self.advance();
-                        args.push(self.expr()?);
-                    }
-                    self.expect_token(TokenType::EvalOut)?;
+                    let name_pos = self.pos_clone();
+                    let name = self.id()?;
+                    let name_pos = name_pos.unwrap();
+                    pos.extend(&name_pos);
+                    let right = self.ast.add(NodeType::ID(name), name_pos);
+                    left = self.ast.add(NodeType::Field { left, right, expr: false }, pos.clone());
+                }
+                TokenType::IndexIn => {
+                    self.advance();
+                    let right = self.expr()?;
+                    self.expect_token(TokenType::IndexOut)?;
                     pos.extend(self.pos().unwrap());
                     self.advance();
-                    left = Node::new(NodeType::Call { head: Box::new(left.clone()), args }, pos.clone());
+                    left = self.ast.add(NodeType::Field { left, right, expr: true }, pos.clone());
                 }
-                // normal call
+                // self call
                 TokenType::Rep => {
                     self.advance();
-                    if self.get() == None { return Err(Error::UnexpectedEOF) }
-                    if let TokenType::ID(id) = self.get_clone().unwrap() {
-                        self.advance();
-                        if self.get() == Some(&TokenType::EvalOut) {
-                            pos.extend(self.pos().unwrap());
-                            self.advance();
-                            left = Node::new(NodeType::Call { head: Box::new(left.clone()), args: vec![] }, pos.clone());
-                            continue
-                        }
-                        let mut args = vec![self.expr()?];
-                        while self.get() == Some(&TokenType::Sep) {
-                            self.advance();
-                            args.push(self.expr()?);
-                        }
-                        self.expect_token(TokenType::EvalOut)?;
-                        pos.extend(self.pos().unwrap());
-                        self.advance();
-                        left = Node::new(NodeType::SelfCall { head: Box::new(left.clone()), field: id, args }, pos.clone());
-                    } else {
-                        return Err(Error::UnexpectedToken(self.get_clone().unwrap()))
-                    }
+                    let field = self.id()?;
+                    let args = self.args(&mut pos)?;
+                    left = self.ast.add(NodeType::SelfCall { head: left, field, args }, pos.clone());
                 }
-                // single table or string arg call
-                TokenType::TableIn | TokenType::String(_) => {
-                    left = Node::new(NodeType::Call { head: Box::new(left.clone()), args: vec![self.atom()?] }, pos.clone());
+                TokenType::EvalIn | TokenType::TableIn | TokenType::String(_) => {
+                    let args = self.args(&mut pos)?;
+                    left = self.ast.add(NodeType::Call { head: left, args }, pos.clone());
                 }
                 _ => break
             }
         }
         Ok(left)
     }
-    pub fn field(&mut self) -> ParseResult {
-        let Some(mut pos) = self.pos_clone() else {
-            return Err(Error::UnexpectedEOF)
-        };
-        let mut left = self.atom()?;
-        while let Some(token) = self.get() {
-            match token {
-                TokenType::Field => {
-                    self.advance();
-                    let right = Box::new(self.atom()?);
-                    pos.extend(right.pos());
-                    left = Node::new(NodeType::Field { left: Box::new(left.clone()), right, expr: false }, pos.clone());
-                }
-                TokenType::IndexIn => {
-                    self.advance();
-                    let right = Box::new(self.expr()?);
-                    self.expect_token(TokenType::IndexOut)?;
-                    pos.extend(self.pos().unwrap());
-                    self.advance();
-                    left = Node::new(NodeType::Field { left: Box::new(left.clone()), right, expr: true }, pos.clone());
-                }
-                _ => break
+    // the arguments of a call, either in parentheses or a single table or string
+    pub fn args(&mut self, pos: &mut Position) -> Result<Vec<NodeId>, Error> {
+        if let Some(TokenType::TableIn | TokenType::String(_)) = self.get() {
+            let arg = self.atom()?;
+            pos.extend(self.ast.pos(arg));
+            return Ok(vec![arg])
+        }
+        self.expect_token(TokenType::EvalIn)?;
+        self.advance();
+        let mut args = vec![];
+        if self.get() != Some(&TokenType::EvalOut) {
+            args.push(self.expr()?);
+            while self.get() == Some(&TokenType::Sep) {
+                self.advance();
+                args.push(self.expr()?);
             }
         }
-        Ok(left)
+        self.expect_token(TokenType::EvalOut)?;
+        pos.extend(self.pos().unwrap());
+        self.advance();
+        Ok(args)
+    }
+    // a node made of the current token
+    pub fn leaf(&mut self, node: NodeType) -> NodeId {
+        let pos = self.pos_clone().unwrap();
+        self.advance();
+        self.ast.add(node, pos)
     }
     pub fn atom(&mut self) -> ParseResult {
         let Some(token) = self.get_clone() else {
-            return Err(Error::UnexpectedEOF);
+            return Err(self.error(ErrorKind::UnexpectedEOF));
         };
         let mut pos = self.pos_clone().unwrap();
-        self.advance();
         match token {
-            TokenType::ID(id) => Ok(Node::new(NodeType::ID(id), pos)),
-            TokenType::Number(v) => Ok(Node::new(NodeType::Number(v), pos)),
-            TokenType::Boolean(v) => Ok(Node::new(NodeType::Boolean(v), pos)),
-            TokenType::String(v) => Ok(Node::new(NodeType::String(v), pos)),
-            TokenType::Nil => Ok(Node::new(NodeType::Nil, pos)),
+            TokenType::ID(id) => Ok(self.leaf(NodeType::ID(id))),
+            TokenType::Integer(v) => Ok(self.leaf(NodeType::Integer(v))),
+            TokenType::Float(v) => Ok(self.leaf(NodeType::Float(v))),
+            TokenType::Boolean(v) => Ok(self.leaf(NodeType::Boolean(v))),
+            TokenType::String(v) => Ok(self.leaf(NodeType::String(v))),
+            TokenType::Nil => Ok(self.leaf(NodeType::Nil)),
+            TokenType::Args => Ok(self.leaf(NodeType::Varargs)),
             TokenType::EvalIn => {
-                let node = Box::new(self.expr()?);
+                self.advance();
+                let node = self.expr()?;
                 self.expect_token(TokenType::EvalOut)?;
                 pos.extend(self.pos().unwrap());
                 self.advance();
-                Ok(Node::new(NodeType::Expr(node), pos))
+                Ok(self.ast.add(NodeType::Expr(node), pos))
+            }
+            TokenType::Fn => {
+                self.advance();
+                let (params, varargs, ret, body) = self.func_body(&mut pos)?;
+                Ok(self.ast.add(NodeType::Lambda { params, varargs, ret, body }, pos))
             }
-            _ => Err(Error::UnexpectedToken(token))
+            TokenType::TableIn => {
+                self.advance();
+                let mut fields = vec![];
+                while self.get() != Some(&TokenType::TableOut) {
+                    match self.get() {
+                        Some(TokenType::IndexIn) => {
+                            self.advance_some()?;
+                            let key = self.expr()?;
+                            self.expect_token(TokenType::IndexOut)?; self.advance_some()?;
+                            self.expect_token(TokenType::Assign)?; self.advance_some()?;
+                            fields.push(TableField::Keyed(key, self.expr()?));
+                        }
+                        Some(TokenType::ID(_)) if self.peek() == Some(&TokenType::Assign) => {
+                            let name = self.id()?;
+                            self.advance_some()?;
+                            fields.push(TableField::Named(name, self.expr()?));
+                        }
+                        Some(_) => fields.push(TableField::Item(self.expr()?)),
+                        None => return Err(self.error(ErrorKind::UnexpectedEOF))
+                    }
+                    if let Some(TokenType::Sep | TokenType::Semi) = self.get() {
+                        self.advance();
+                    } else { break }
+                }
+                self.expect_token(TokenType::TableOut)?;
+                pos.extend(self.pos().unwrap());
+                self.advance();
+                Ok(self.ast.add(NodeType::Table(fields), pos))
+            }
+            // the token isn't skipped so the statement after it can still be parsed
+            _ => Err(self.error(ErrorKind::UnexpectedToken(token)))
         }
     }
 }
 
-pub fn parse(path: &String, tokens: Vec<Token>) -> ParseResult {
-    Parser::new(path, tokens).parse()
+// the tree is complete when there are no errors, otherwise it has error nodes where statements couldn't be parsed
+// tokens are pulled from the lexer as they're needed
+pub fn parse(file: FileId, tokens: impl Iterator<Item = Result<Token, Error>>) -> (Ast, Vec<Error>) {
+    let mut parser = Parser::new(file, tokens);
+    parser.parse();
+    // errors after a lexing error only come from the input ending early
+    if let Some((err, count)) = parser.lex_error {
+        parser.errors.truncate(count);
+        parser.errors.push(err);
+    }
+    (parser.ast, parser.errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Range;
+    use crate::scanning::{lexer::Lexer, emit::{self, Value}};
+
+    fn ast(text: &str) -> Ast {
+        let (ast, errors) = parse(0, Lexer::new(0, text));
+        assert!(errors.is_empty(), "{errors:?}");
+        ast
+    }
+    fn stats(ast: &Ast) -> Vec<String> {
+        let NodeType::Chunk(stats) = ast.get(ast.root()).node() else { panic!() };
+        stats.iter().map(|&x| ast.get(x).node().name().to_string()).collect()
+    }
+    // the line of every error
+    fn errors(text: &str) -> Vec<usize> {
+        let lines = |err: &Error| text[..err.pos().unwrap().span().start].matches('\n').count() + 1;
+        parse(0, Lexer::new(0, text)).1.iter().map(lines).collect()
+    }
+
+    // every node lies inside the node it belongs to
+    fn check_spans(value: &Value, parent: &Range<usize>) {
+        match value {
+            Value::Item { kind, span, fields } => {
+                assert!(parent.start <= span.start && span.end <= parent.end, "{kind} {span:?} is outside of {parent:?}");
+                for (_, field) in fields { check_spans(field, span) }
+            }
+            Value::List(values) => for value in values { check_spans(value, parent) },
+            _ => {}
+        }
+    }
+    #[test]
+    fn spans_nest() {
+        let texts = [
+            include_str!("../../samples/set/set.luo"),
+            "a, b = 1, 2\nc.d = e\nwhile x do print(x) end\nrepeat local y = 1 until y\nfor i = 1, 2 do end\nfor k, v in pairs(t) do end",
+            "if a then print(1) elseif b then c() else d() end\nlocal t = {[k] = v, n = 1, 2; f(...)}\nlocal function f(a: number, ...) -> number return a end",
+        ];
+        for text in texts {
+            let ast = ast(text);
+            check_spans(&emit::node(&ast, ast.root()), &(0..text.len()));
+        }
+    }
+    #[test]
+    fn chained_suffixes() {
+        let ast = ast("print(f().x)\nf(...)[1].x = 1\na:b().c, d = 1, 2\nlocal e = a.b:c{}.d\"s\"[1]\na.b:c{}.d\"s\"(1)");
+        assert_eq!(stats(&ast), ["call", "assignment", "assignments", "local assignment", "call"]);
+        assert_eq!(ast.format(ast.root(), 0, false), "print(f().x)\nf(...)[1].x = 1\na:b().c, d = 1, 2\nlocal e = a.b:c({}).d(\"s\")[1]\na.b:c({}).d(\"s\")(1)");
+    }
+    #[test]
+    fn field_needs_a_name() {
+        assert_eq!(errors("a.\"s\" = 1"), [1]);
+        assert_eq!(errors("a.1 = 1"), [1]);
+        assert_eq!(errors("print(a.)"), [1]);
+    }
+    #[test]
+    fn recovery_keeps_the_next_statement() {
+        assert_eq!(errors("local x =\nlocal y = 1\nprint(y +)\nlocal z = = 3"), [2, 3, 4]);
+        assert_eq!(errors("if then end\nprint(1 +)"), [1, 2]);
+        assert_eq!(errors("while x + do\n  local y = = 1\nend\nprint(x)"), [1, 2]);
+        assert_eq!(errors("if a then b() elseif then c() else d() end\nf(,)"), [1, 2]);
+    }
 }
\ No newline at end of file
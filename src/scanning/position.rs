@@ -1,15 +1,77 @@
-use std::ops::Range;
+use std::{ops::Range, fmt::Display};
+
+// index of a file in the `SourceMap`
+pub type FileId = usize;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Position {
-    ln: Range<usize>,
-    col: Range<usize>,
+    file: FileId,
+    // byte offsets into the file's text
+    span: Range<usize>,
 }
 impl Position {
-    pub fn new(ln: Range<usize>, col: Range<usize>) -> Self { Self { ln, col } }
-    pub fn zero() -> Self { Self { ln: 0..1, col: 0..1 } }
+    pub fn new(file: FileId, span: Range<usize>) -> Self { Self { file, span } }
+    pub fn zero(file: FileId) -> Self { Self { file, span: 0..0 } }
+    pub fn file(&self) -> FileId { self.file }
+    pub fn span(&self) -> &Range<usize> { &self.span }
     pub fn extend(&mut self, pos: &Position) {
-        self.ln.end = pos.ln.end;
-        self.col.end = pos.col.end;
+        self.span.end = self.span.end.max(pos.span.end);
+    }
+}
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.span.start, self.span.end)
+    }
+}
+
+pub struct SourceFile {
+    path: String,
+    text: String,
+    // byte offset every line starts at
+    lines: Vec<usize>,
+}
+impl SourceFile {
+    pub fn new(path: &str, text: String) -> Self {
+        let lines = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Self { path: path.to_string(), text, lines }
+    }
+    pub fn path(&self) -> &str { &self.path }
+    pub fn text(&self) -> &str { &self.text }
+    // zero based line and column in chars, a byte order mark doesn't take up a column
+    pub fn location(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let ln = self.lines.partition_point(|&start| start <= offset) - 1;
+        let col = self.text.get(self.lines[ln]..offset).map_or(0, |x| x.trim_start_matches('\u{feff}').chars().count());
+        (ln, col)
+    }
+    // editors speaking the language server protocol count columns in UTF-16 code units
+    pub fn utf16_location(&self, offset: usize) -> (usize, usize) {
+        let (ln, col) = self.location(offset);
+        (ln, self.line(ln).chars().take(col).map(char::len_utf16).sum())
+    }
+    // the text of a line without its line break
+    pub fn line(&self, ln: usize) -> &str {
+        let Some(&start) = self.lines.get(ln) else { return "" };
+        let end = self.lines.get(ln + 1).copied().unwrap_or(self.text.len());
+        self.text[start..end].trim_start_matches('\u{feff}').trim_end_matches(['\n', '\r'])
+    }
+}
+
+// owns the text of every loaded file, positions only refer to them by id
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+impl SourceMap {
+    pub fn add(&mut self, path: &str, text: String) -> FileId {
+        self.files.push(SourceFile::new(path, text));
+        self.files.len() - 1
+    }
+    pub fn get(&self, file: FileId) -> Option<&SourceFile> { self.files.get(file) }
+    // `path:ln:col` with one based line and column
+    pub fn describe(&self, pos: &Position) -> String {
+        let Some(file) = self.get(pos.file) else { return pos.to_string() };
+        let (ln, col) = file.location(pos.span.start);
+        format!("{}:{}:{}", file.path, ln + 1, col + 1)
     }
 }
\ No newline at end of file
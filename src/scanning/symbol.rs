@@ -0,0 +1,50 @@
+use std::{borrow::Cow, collections::HashMap, fmt::{Debug, Display}, sync::{Mutex, OnceLock}};
+
+// an interned string, compared and hashed by its index
+// like lua strings they're bytes, only names are guaranteed to be valid utf-8
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+pub struct Interner {
+    ids: HashMap<&'static [u8], Symbol>,
+    strings: Vec<&'static [u8]>
+}
+impl Interner {
+    pub fn intern(&mut self, string: &[u8]) -> Symbol {
+        if let Some(&symbol) = self.ids.get(string) { return symbol }
+        // the strings live as long as the program, so symbols can hand them out without a lock guard
+        let string: &'static [u8] = Box::leak(string.to_vec().into_boxed_slice());
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(string);
+        self.ids.insert(string, symbol);
+        symbol
+    }
+    pub fn get(&self, symbol: Symbol) -> &'static [u8] { self.strings[symbol.0 as usize] }
+}
+// shared by every file the compiler reads
+pub fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| Mutex::new(Interner::default()))
+}
+
+impl Symbol {
+    pub fn intern(string: &str) -> Self { Self::intern_bytes(string.as_bytes()) }
+    pub fn intern_bytes(bytes: &[u8]) -> Self { interner().lock().unwrap().intern(bytes) }
+    pub fn as_bytes(&self) -> &'static [u8] { interner().lock().unwrap().get(*self) }
+    // bytes that aren't valid utf-8 are replaced
+    pub fn as_str(&self) -> Cow<'static, str> { String::from_utf8_lossy(self.as_bytes()) }
+}
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Self { Self::intern(string) }
+}
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
\ No newline at end of file
@@ -1,23 +1,29 @@
 use std::fmt::{Debug};
-use super::position::Position;
+use super::{position::Position, symbol::Symbol};
+
+// unary operators bind tighter than every binary operator but `^`
+pub const UNARY_PRIORITY: u8 = 12;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
-    ID(String), Number(f64), Boolean(bool), String(String), Nil,
-//  +    -    *    /    %    ^    #
-    Add, Sub, Mul, Div, Mod, Pow, Len,
+    ID(Symbol), Integer(i64), Float(f64), Boolean(bool), String(Symbol), Nil,
+//  +    -    *    /    %    ^    #    //
+    Add, Sub, Mul, Div, Mod, Pow, Len, IDiv,
+//  &     ~     <<   >>
+    BAnd, BXor, Shl, Shr,
 //  ==  ~=  <   >   <=  >=  =
     EQ, NE, LT, GT, LE, GE, Assign,
 //  (       )        {        }         [        ]
     EvalIn, EvalOut, TableIn, TableOut, IndexIn, IndexOut,
-//  :    ,    .      ..     ...
-    Rep, Sep, Field, Concat, Args,
-    And, Break, Do, Else, Elseif, End, For, Fn, If, In, Local,
-    Not, Or, Return, Then, While
+//  :    ,    ;     .      ..     ...   ->     ?    |    ::
+    Rep, Sep, Semi, Field, Concat, Args, Arrow, Opt, BOr, Label,
+    And, Break, Do, Else, Elseif, End, For, Fn, Goto, If, In, Local,
+    Not, Or, Repeat, Return, Then, Until, While,
+    Meta, Implement
 }
 impl TokenType {
-    pub fn from_name(id: String) -> Self {
-        match id.as_str() {
+    pub fn from_name(id: &str) -> Self {
+        match id {
             "true" => Self::Boolean(true),
             "false" => Self::Boolean(false),
             "nil" => Self::Nil,
@@ -29,23 +35,30 @@ impl TokenType {
             "end" => Self::End,
             "for" => Self::For,
             "function" => Self::Fn,
+            "goto" => Self::Goto,
             "if" => Self::If,
             "in" => Self::In,
             "local" => Self::Local,
             "not" => Self::Not,
             "or" => Self::Or,
+            "repeat" => Self::Repeat,
             "return" => Self::Return,
             "then" => Self::Then,
+            "until" => Self::Until,
             "while" => Self::While,
-            _ => Self::ID(id)
+            "meta" => Self::Meta,
+            "implement" => Self::Implement,
+            _ => Self::ID(Symbol::intern(id))
         }
     }
     pub fn display(&self) -> String {
         match self {
-            Self::ID(v) => format!("{v}"),
-            Self::Number(v) => format!("{v}"),
+            Self::ID(v) => v.to_string(),
+            Self::Integer(v) => format!("{v}"),
+            // floats always keep a fraction or an exponent
+            Self::Float(v) => format!("{v:?}"),
             Self::Boolean(v) => format!("{v}"),
-            Self::String(v) => format!("{v}"),
+            Self::String(v) => v.to_string(),
             Self::Nil => "nil".to_string(),
             Self::Add => "+".to_string(),
             Self::Sub => "-".to_string(),
@@ -53,6 +66,11 @@ impl TokenType {
             Self::Div => "/".to_string(),
             Self::Mod => "%".to_string(),
             Self::Pow => "^".to_string(),
+            Self::IDiv => "//".to_string(),
+            Self::BAnd => "&".to_string(),
+            Self::BXor => "~".to_string(),
+            Self::Shl => "<<".to_string(),
+            Self::Shr => ">>".to_string(),
             Self::Len => "#".to_string(),
             Self::EQ => "==".to_string(),
             Self::NE => "~=".to_string(),
@@ -63,15 +81,20 @@ impl TokenType {
             Self::Assign => "=".to_string(),
             Self::EvalIn => "(".to_string(),
             Self::EvalOut => ")".to_string(),
-            Self::TableIn => "{{".to_string(),
-            Self::TableOut => "}}".to_string(),
+            Self::TableIn => "{".to_string(),
+            Self::TableOut => "}".to_string(),
             Self::IndexIn => "[".to_string(),
             Self::IndexOut => "]".to_string(),
             Self::Rep => ":".to_string(),
             Self::Sep => ",".to_string(),
+            Self::Semi => ";".to_string(),
             Self::Field => ".".to_string(),
             Self::Concat => "..".to_string(),
             Self::Args => "...".to_string(),
+            Self::Arrow => "->".to_string(),
+            Self::Opt => "?".to_string(),
+            Self::BOr => "|".to_string(),
+            Self::Label => "::".to_string(),
             Self::And => "and".to_string(),
             Self::Break => "break".to_string(),
             Self::Do => "do".to_string(),
@@ -80,39 +103,111 @@ impl TokenType {
             Self::End => "end".to_string(),
             Self::For => "for".to_string(),
             Self::Fn => "function".to_string(),
+            Self::Goto => "goto".to_string(),
             Self::If => "if".to_string(),
             Self::In => "in".to_string(),
             Self::Local => "local".to_string(),
             Self::Not => "not".to_string(),
             Self::Or => "or".to_string(),
+            Self::Repeat => "repeat".to_string(),
             Self::Return => "return".to_string(),
             Self::Then => "then".to_string(),
+            Self::Until => "until".to_string(),
             Self::While => "while".to_string(),
+            Self::Meta => "meta".to_string(),
+            Self::Implement => "implement".to_string(),
         }
     }
     pub fn name(&self) -> String {
         match self {
             Self::ID(_) => "identifier".to_string(),
-            Self::Number(_) => "number".to_string(),
+            Self::Integer(_) | Self::Float(_) => "number".to_string(),
             Self::Boolean(_) => "boolean".to_string(),
             Self::String(_) => "string".to_string(),
             Self::Nil => "nil".to_string(),
             _ => format!("'{}'", self.display())
         }
     }
+    // left and right priority of binary operators, same as in the reference implementation
+    // a higher right priority makes the operator left associative, a lower one right associative
+    pub fn binary_priority(&self) -> (u8, u8) {
+        match self {
+            Self::Or => (1, 1),
+            Self::And => (2, 2),
+            Self::EQ | Self::NE | Self::LT | Self::GT | Self::LE | Self::GE => (3, 3),
+            Self::BOr => (4, 4),
+            Self::BXor => (5, 5),
+            Self::BAnd => (6, 6),
+            Self::Shl | Self::Shr => (7, 7),
+            Self::Concat => (9, 8),
+            Self::Add | Self::Sub => (10, 10),
+            Self::Mul | Self::Div | Self::IDiv | Self::Mod => (11, 11),
+            Self::Pow => (14, 13),
+            // not a binary operator
+            _ => (0, 0)
+        }
+    }
+    pub fn is_unary(&self) -> bool {
+        matches!(self, Self::Not | Self::Sub | Self::Len | Self::BXor)
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    Whitespace, Newline, LineComment, BlockComment,
+    // `---` comments
+    DocComment,
+    Bom, Shebang
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    kind: TriviaKind,
+    text: String
 }
+impl Trivia {
+    pub fn new(kind: TriviaKind, text: &str) -> Self { Self { kind, text: text.to_string() } }
+    pub fn kind(&self) -> TriviaKind { self.kind }
+    pub fn text(&self) -> &str { &self.text }
+}
+
 #[derive(Clone)]
 pub struct Token {
     token: TokenType,
-    pos: Position
+    pos: Position,
+    // only filled in by the lossless lexer
+    text: String,
+    leading: Vec<Trivia>,
+    trailing: Vec<Trivia>
 }
 impl Token {
-    pub fn new(token: TokenType, pos: Position) -> Self { Self { token, pos } }
+    pub fn new(token: TokenType, pos: Position) -> Self {
+        Self { token, pos, text: String::new(), leading: vec![], trailing: vec![] }
+    }
+    pub fn with_trivia(mut self, text: String, leading: Vec<Trivia>, trailing: Vec<Trivia>) -> Self {
+        self.text = text;
+        self.leading = leading;
+        self.trailing = trailing;
+        self
+    }
     pub fn token(&self) -> &TokenType { &self.token }
     pub fn pos(&self) -> &Position { &self.pos }
+    pub fn text(&self) -> &str { &self.text }
+    pub fn leading(&self) -> &[Trivia] { &self.leading }
+    pub fn trailing(&self) -> &[Trivia] { &self.trailing }
 }
 impl Debug for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self.token)
     }
+}
+
+// the original text of a lossless token stream
+pub fn reproduce(tokens: &[Token], eof: &[Trivia]) -> String {
+    let mut text = String::new();
+    for token in tokens {
+        token.leading.iter().for_each(|x| text.push_str(&x.text));
+        text.push_str(&token.text);
+        token.trailing.iter().for_each(|x| text.push_str(&x.text));
+    }
+    eof.iter().for_each(|x| text.push_str(&x.text));
+    text
 }
\ No newline at end of file